@@ -0,0 +1,319 @@
+//! Contains an async variant of [`Epd`] which awaits the display instead of blocking the thread.
+//!
+//! This module is only available with the `async` feature. The delays and the busy pin are awaited
+//! on the [`tokio`](https://docs.rs/tokio) timer, so the operations have to run within a tokio
+//! runtime.
+use crate::epd_configs::EpdConfig;
+use crate::interface::EpdInterface;
+use crate::recorder::Recorder;
+use crate::registers::{BorderColor, PanelSetting};
+use crate::rpi_helper::{HardwareConfig, RpiGpio};
+use crate::wait::Wait;
+use crate::{
+    Epd, EpdError, EpdState, Frame, FrameCache, GhostingPolicy, IdlePolicy, RefreshMode, Revision,
+    Rotation, Status, TemperatureCompensation,
+};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Represents a E-Paper Display driven from async code.
+///
+/// `AsyncEpd` runs the same command sequences as [`Epd`], but its operations return futures which
+/// wait for the display with [`tokio::time::sleep`] instead of sleeping the calling thread. The busy pin is polled at
+/// the busy poll interval while the display refreshes, so other tasks keep running in the
+/// meantime.
+///
+/// Like [`Epd`], the display is put to sleep when `AsyncEpd` is dropped, which blocks the thread.
+/// Await [`sleep`](AsyncEpd::sleep) before dropping it to avoid stalling the executor.
+///
+/// # Examples
+///
+/// ```no_run
+/// use waveshare_rpi::{async_epd::AsyncEpd, epd_configs::epd7in5_v2::EPD_CONFIG};
+///
+/// # async fn run() -> Result<(), waveshare_rpi::EpdError> {
+/// let mut epd = AsyncEpd::new(EPD_CONFIG).await?;
+/// let data = vec![0x00; epd.image_buffer_size()];
+/// epd.display(&data).await?;
+/// epd.sleep().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncEpd<I: EpdInterface = RpiGpio> {
+    epd: Epd<I>,
+}
+
+impl AsyncEpd {
+    /// Creates a new instance of `AsyncEpd` with the config of a Waveshare E-Paper Display.
+    pub async fn new(config: EpdConfig) -> Result<Self, EpdError> {
+        Self::with_interface(config, RpiGpio::new()?).await
+    }
+
+    /// Creates a new instance of `AsyncEpd` for a display wired up as described by
+    /// `hardware_config`.
+    pub async fn with_hardware_config(
+        config: EpdConfig,
+        hardware_config: HardwareConfig,
+    ) -> Result<Self, EpdError> {
+        Self::with_interface(config, RpiGpio::with_config(hardware_config)?).await
+    }
+}
+
+impl<I: EpdInterface> AsyncEpd<I> {
+    /// Creates a new instance of `AsyncEpd` which talks to the display through `interface`.
+    pub async fn with_interface(config: EpdConfig, interface: I) -> Result<Self, EpdError> {
+        let mut epd = Epd::uninitialized(config, interface);
+        epd.init_with::<Timer>().await?;
+        Ok(Self { epd })
+    }
+
+    /// Returns a reference to the underlying hardware interface.
+    pub fn interface(&self) -> &I {
+        self.epd.interface()
+    }
+
+    /// Returns a mutable reference to the underlying hardware interface.
+    pub fn interface_mut(&mut self) -> &mut I {
+        self.epd.interface_mut()
+    }
+
+    /// Sets how long to wait for the EPD to stop being busy before giving up with
+    /// [`EpdError::BusyTimeout`]. `None` waits forever. Defaults to 30 seconds.
+    pub fn set_busy_timeout(&mut self, timeout: Option<Duration>) {
+        self.epd.set_busy_timeout(timeout);
+    }
+
+    /// Sets the interval at which the busy pin is re-read while waiting for the EPD. Defaults to
+    /// 100 ms.
+    pub fn set_busy_poll_interval(&mut self, interval: Duration) {
+        self.epd.set_busy_poll_interval(interval);
+    }
+
+    /// Returns the current lifecycle state of the display.
+    pub fn state(&self) -> EpdState {
+        self.epd.state()
+    }
+
+    /// Returns the refresh mode used by [`display`](AsyncEpd::display) and
+    /// [`clear`](AsyncEpd::clear).
+    pub fn refresh_mode(&self) -> RefreshMode {
+        self.epd.refresh_mode()
+    }
+
+    /// Sets the refresh mode used by [`display`](AsyncEpd::display) and
+    /// [`clear`](AsyncEpd::clear). See [`Epd::set_refresh_mode`].
+    pub fn set_refresh_mode(&mut self, mode: RefreshMode) -> Result<(), EpdError> {
+        self.epd.set_refresh_mode(mode)
+    }
+
+    /// Sets how full refreshes are adjusted to the temperature. See
+    /// [`Epd::set_temperature_compensation`].
+    pub fn set_temperature_compensation(&mut self, compensation: Option<TemperatureCompensation>) {
+        self.epd.set_temperature_compensation(compensation);
+    }
+
+    /// Lets [`display`](AsyncEpd::display) update only the changed part of an image with a partial
+    /// refresh. See [`Epd::set_auto_partial`].
+    pub fn set_auto_partial(&mut self, max_area: Option<f32>) -> Result<(), EpdError> {
+        self.epd.set_auto_partial(max_area)
+    }
+
+    /// Sets when partial and fast refreshes are replaced with a normal full refresh to clear
+    /// ghosting. See [`GhostingPolicy`].
+    pub fn set_ghosting_policy(&mut self, policy: GhostingPolicy) {
+        self.epd.set_ghosting_policy(policy);
+    }
+
+    /// Powers down the display according to `policy` once it has been idle for a while. See
+    /// [`Epd::set_idle_policy`].
+    ///
+    /// The policy is applied by [`poll_idle`](AsyncEpd::poll_idle), which can be awaited in a loop
+    /// of its own task until the [`idle_deadline`](AsyncEpd::idle_deadline).
+    pub fn set_idle_policy(&mut self, policy: Option<IdlePolicy>) {
+        self.epd.set_idle_policy(policy);
+    }
+
+    /// Returns when the display will be due to be powered down by the idle policy. See
+    /// [`Epd::idle_deadline`].
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        self.epd.idle_deadline()
+    }
+
+    /// Powers down the display if it has been idle for longer than the timeout of the idle policy.
+    /// Returns whether the display was powered down.
+    pub async fn poll_idle(&mut self) -> Result<bool, EpdError> {
+        self.epd.poll_idle_with::<Timer>().await
+    }
+
+    /// Drives the PWR pin low, which cuts the power of the display until the next operation.
+    pub fn cut_power(&mut self) -> Result<(), EpdError> {
+        self.epd.cut_power()
+    }
+
+    /// Writes the traffic between `AsyncEpd` and the display to `recorder`. See
+    /// [`Epd::set_recorder`].
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        self.epd.set_recorder(recorder);
+    }
+
+    /// Returns the panel setting written by the init commands. See [`Epd::panel_setting`].
+    pub fn panel_setting(&self) -> Option<PanelSetting> {
+        self.epd.panel_setting()
+    }
+
+    /// Replaces the panel setting written by the init commands. See [`Epd::set_panel_setting`].
+    pub fn set_panel_setting(&mut self, setting: Option<PanelSetting>) -> Result<(), EpdError> {
+        self.epd.set_panel_setting(setting)
+    }
+
+    /// Sets the color of the border around the active area of the display. See
+    /// [`Epd::set_border`].
+    pub fn set_border(&mut self, border: Option<BorderColor>) -> Result<(), EpdError> {
+        self.epd.set_border(border)
+    }
+
+    /// Sets the VCOM DC level in volts. See [`Epd::set_vcom`].
+    pub fn set_vcom(&mut self, volts: Option<f32>) -> Result<(), EpdError> {
+        self.epd.set_vcom(volts)
+    }
+
+    /// Changes the resolution of the display. See [`Epd::set_resolution`].
+    pub fn set_resolution(&mut self, width: usize, height: usize) -> Result<(), EpdError> {
+        self.epd.set_resolution(width, height)
+    }
+
+    /// Returns the image last shown on the display. See [`Epd::frame_cache`].
+    pub fn frame_cache(&self) -> Option<&FrameCache> {
+        self.epd.frame_cache()
+    }
+
+    /// Tells the `AsyncEpd` which image is shown on the display. See [`Epd::set_frame_cache`].
+    pub fn set_frame_cache(&mut self, cache: FrameCache) {
+        self.epd.set_frame_cache(cache);
+    }
+
+    /// Returns the clockwise rotation of the images shown on the display.
+    pub fn rotation(&self) -> Rotation {
+        self.epd.rotation()
+    }
+
+    /// Rotates all images passed to [`display`](AsyncEpd::display) and
+    /// [`display_region`](AsyncEpd::display_region) clockwise by `rotation`. See
+    /// [`Epd::set_rotation`].
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.epd.set_rotation(rotation);
+    }
+
+    /// Mirrors all images passed to [`display`](AsyncEpd::display) and
+    /// [`display_region`](AsyncEpd::display_region) horizontally and/or vertically. See
+    /// [`Epd::set_mirroring`].
+    pub fn set_mirroring(&mut self, horizontal: bool, vertical: bool) {
+        self.epd.set_mirroring(horizontal, vertical);
+    }
+
+    /// Returns the width of images passed to [`display`](AsyncEpd::display) in pixels. See
+    /// [`Epd::width`].
+    pub fn width(&self) -> usize {
+        self.epd.width()
+    }
+
+    /// Returns the height of images passed to [`display`](AsyncEpd::display) in pixels. See
+    /// [`Epd::height`].
+    pub fn height(&self) -> usize {
+        self.epd.height()
+    }
+
+    /// Reads the temperature in °C from the display controller's internal sensor. See
+    /// [`Epd::temperature`].
+    pub async fn temperature(&mut self) -> Result<f32, EpdError> {
+        self.epd.temperature_with::<Timer>().await
+    }
+
+    /// Reads the status flags of the display controller. See [`Epd::status`].
+    pub async fn status(&mut self) -> Result<Status, EpdError> {
+        self.epd.status_with::<Timer>().await
+    }
+
+    /// Reads the revision numbers of the display controller. See [`Epd::revision`].
+    pub async fn revision(&mut self) -> Result<Revision, EpdError> {
+        self.epd.revision_with::<Timer>().await
+    }
+
+    /// Returns the number of bytes that the EPD takes in for displaying an image.
+    pub fn image_buffer_size(&self) -> usize {
+        self.epd.image_buffer_size()
+    }
+
+    /// Resets the EPD and sends it the commands to initialize it for the current refresh mode.
+    pub async fn init(&mut self) -> Result<(), EpdError> {
+        self.epd.init_with::<Timer>().await
+    }
+
+    /// Brings the EPD back to the [`Ready`](EpdState::Ready) state. See [`Epd::wake`].
+    pub async fn wake(&mut self) -> Result<(), EpdError> {
+        self.epd.wake_with::<Timer>().await
+    }
+
+    /// Waits on the busy pin and returns once the EPD stops being busy.
+    /// Returns `Err(EpdError::BusyTimeout)` if the EPD is still busy once the busy timeout elapses.
+    pub async fn read_busy(&mut self) -> Result<(), EpdError> {
+        self.epd.read_busy_with::<Timer>().await
+    }
+
+    /// Clears the screen by setting all of its pixels to white.
+    pub async fn clear(&mut self) -> Result<(), EpdError> {
+        self.epd.clear_with::<Timer>().await
+    }
+
+    /// Takes in image data (represented in an array of `u8` or a [`Frame`]) and displays it on the
+    /// EPD. See [`Epd::display`] for the image data format.
+    pub async fn display<'a>(&mut self, frame: impl Into<Frame<'a>>) -> Result<(), EpdError> {
+        self.epd.display_with::<Timer>(frame.into()).await
+    }
+
+    /// Updates a rectangular region of the EPD using a partial refresh. See
+    /// [`Epd::display_region`].
+    pub async fn display_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<(), EpdError> {
+        self.epd
+            .display_region_with::<Timer>(x, y, width, height, data)
+            .await
+    }
+
+    /// Removes ghosting and burn-in by driving the whole display black and white a few times. See
+    /// [`Epd::deep_clean`].
+    pub async fn deep_clean(&mut self) -> Result<(), EpdError> {
+        self.epd.deep_clean_with::<Timer>().await
+    }
+
+    /// Powers off the display while keeping its settings, so it can be powered on again quickly.
+    pub async fn power_off(&mut self) -> Result<(), EpdError> {
+        self.epd.power_off_with::<Timer>().await
+    }
+
+    /// Puts the display to a low power consumption state. See [`Epd::sleep`].
+    pub async fn sleep(&mut self) -> Result<(), EpdError> {
+        self.epd.sleep_with::<Timer>().await
+    }
+}
+
+/// Waits on the tokio timer. The busy pin is re-read at the busy poll interval, as the interfaces
+/// can only wait for its edges by blocking the thread.
+struct Timer;
+
+impl Wait for Timer {
+    fn sleep(duration: Duration) -> impl Future<Output = ()> {
+        tokio::time::sleep(duration)
+    }
+
+    async fn busy_edge<I: EpdInterface>(_: &mut I, timeout: Duration) -> Result<(), EpdError> {
+        tokio::time::sleep(timeout).await;
+        Ok(())
+    }
+}
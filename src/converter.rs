@@ -1,16 +1,20 @@
 //! Contains functions for converting images and text to EPD format.
 use crate::EpdConfig;
-use image::{self, imageops::*, DynamicImage, GenericImage, ImageBuffer, Luma};
+use image::{self, imageops::*, DynamicImage, GenericImage, ImageBuffer, Luma, Rgb};
 use ril::{BitPixel, Draw, Font, Image, TextSegment};
 
-/// Color mode for the converted image data. Currently unutilized.
+/// Color mode for the converted image data.
 #[derive(Default, PartialEq)]
 pub enum ColorMode {
     /// For displays which only displays black and white.
     #[default]
     BlackWhite,
-    /// For displays which displays black, white and red.
+    /// For displays which displays black, white and red. Use [`image_to_epd_planes`] to convert
+    /// images in this mode.
     BlackWhiteRed,
+    /// For displays which displays 4 levels of gray. Use [`image_to_epd_planes`] to convert images
+    /// in this mode.
+    Gray4,
 }
 
 /// Cropping mode for converting images to EPD format.
@@ -47,7 +51,7 @@ pub enum RotationMode {
 /// |---|---|---|---|
 /// | `crop_mode` | [`CropMode`] | Modes to pre-process the image to fit on the display. | [`Center`](CropMode::Center) |
 /// | `rotation_mode` | [`RotationMode`] | How to rotate the image before pre-processing. | [`Automatic`](RotationMode::Automatic) |
-/// | `color_mode` | [`ColorMode`] | Unutilized at the moment, sets the color mode of the display. | [`BlackWhite`](ColorMode::BlackWhite) |
+/// | `color_mode` | [`ColorMode`] | Sets the color mode of the display. | [`BlackWhite`](ColorMode::BlackWhite) |
 /// | `epd_width` | `usize` | Width of the EPD display measured in pixels. **Do not set this value explicitly!**. Use `load_epd_config` instead if you want to set this value. | 0 |
 /// | `epd_height` | `usize` | Height of the EPD display measured in pixels. **Do not set this value explicitly!**. Use `load_epd_config` instead if you want to set this value. | 0 |
 ///
@@ -116,23 +120,66 @@ impl EpdImageOptions {
     }
 }
 
-fn center_and_pad(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+/// Color map quantizing to the 4 levels of gray of [`ColorMode::Gray4`].
+struct Gray4Map;
+
+impl ColorMap for Gray4Map {
+    type Color = Luma<u8>;
+
+    fn index_of(&self, color: &Luma<u8>) -> usize {
+        ((color.0[0] as usize + 42) / 85).min(3)
+    }
+
+    fn map_color(&self, color: &mut Luma<u8>) {
+        color.0[0] = self.index_of(color) as u8 * 85;
+    }
+}
+
+/// Color map quantizing to the colors of [`ColorMode::BlackWhiteRed`], in the order black,
+/// white and red.
+struct BlackWhiteRedMap;
+
+impl BlackWhiteRedMap {
+    const COLORS: [Rgb<u8>; 3] = [Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([255, 0, 0])];
+}
+
+impl ColorMap for BlackWhiteRedMap {
+    type Color = Rgb<u8>;
+
+    fn index_of(&self, color: &Rgb<u8>) -> usize {
+        let distance = |other: &Rgb<u8>| -> i32 {
+            color
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .map(|(&a, &b)| (a as i32 - b as i32).pow(2))
+                .sum()
+        };
+        (0..Self::COLORS.len())
+            .min_by_key(|&i| distance(&Self::COLORS[i]))
+            .unwrap()
+    }
+
+    fn map_color(&self, color: &mut Rgb<u8>) {
+        *color = Self::COLORS[self.index_of(color)];
+    }
+}
+
+fn center_and_pad(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
     // Process the image
     let img = img.resize(
         options.epd_width.try_into().unwrap(),
         options.epd_height.try_into().unwrap(),
         FilterType::Lanczos3,
     );
-    let img = img.grayscale();
-    let mut img = img.into_luma8();
-    dither(&mut img, &BiLevel);
+    let img = img.into_rgb8();
 
-    let mut new_canvas: ImageBuffer<Luma<u8>, Vec<u8>> =
+    let mut new_canvas: ImageBuffer<Rgb<u8>, Vec<u8>> =
         ImageBuffer::new(options.epd_width as u32, options.epd_height as u32);
 
     // initialize canvas to white
     for pixel in new_canvas.pixels_mut() {
-        *pixel = Luma([255]);
+        *pixel = Rgb([255, 255, 255]);
     }
 
     if img.height() < options.epd_height as u32 {
@@ -148,16 +195,35 @@ fn center_and_pad(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<L
     new_canvas
 }
 
-fn crop_to_fit(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<Luma<u8>, Vec<u8>> {
+fn crop_to_fit(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
     let img = img.resize_to_fill(
         options.epd_width.try_into().unwrap(),
         options.epd_height.try_into().unwrap(),
         FilterType::Lanczos3,
     );
-    let img = img.grayscale();
-    let mut img = img.into_luma8();
-    dither(&mut img, &BiLevel);
-    img
+    img.into_rgb8()
+}
+
+/// Opens the image and rotates, resizes and crops it to the size of the EPD.
+fn load_image(
+    filepath: &str,
+    options: &EpdImageOptions,
+) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, Box<dyn std::error::Error>> {
+    if options.epd_width == 0 || options.epd_height == 0 {
+        return Err("epd_width and epd_height must be greater than 0".into());
+    }
+
+    let mut img = image::open(filepath)?;
+
+    // rotate image if necessary
+    if options.check_rotation(&img) {
+        img = img.rotate90();
+    }
+
+    Ok(match options.crop_mode {
+        CropMode::Center => center_and_pad(options, img),
+        CropMode::CropToFit => crop_to_fit(options, img),
+    })
 }
 
 /// Convert an image to EPD format to be displayed on the e-paper display. Uses the
@@ -181,28 +247,18 @@ fn crop_to_fit(options: &EpdImageOptions, img: DynamicImage) -> ImageBuffer<Luma
 ///
 /// # To-dos
 ///
-/// * Add support for `ColorMode::BlackWhiteRed`.
 /// * Reimplement with ril to support interoperability with `text_to_epd`.
 /// * Integrate this function into te Epd struct via a trait (toggleable with a feature).
 pub fn image_to_epd(
     filepath: &str,
     options: EpdImageOptions,
 ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    if options.epd_width == 0 || options.epd_height == 0 {
-        return Err("epd_width and epd_height must be greater than 0".into());
-    }
-
-    let mut img = image::open(filepath)?;
-
-    // rotate image if necessary
-    if options.check_rotation(&img) {
-        img = img.rotate90();
+    if options.color_mode != ColorMode::BlackWhite {
+        return Err("color mode produces multiple planes, use image_to_epd_planes instead".into());
     }
 
-    let img = match options.crop_mode {
-        CropMode::Center => center_and_pad(&options, img),
-        CropMode::CropToFit => crop_to_fit(&options, img),
-    };
+    let mut img = DynamicImage::ImageRgb8(load_image(filepath, &options)?).into_luma8();
+    dither(&mut img, &BiLevel);
 
     // convert to epd format
     let final_img = img.into_raw();
@@ -219,6 +275,70 @@ pub fn image_to_epd(
     Ok(data)
 }
 
+/// Convert an image to the two bit-planes used by the EPD for color modes with more than two
+/// colors. Works like [`image_to_epd`] otherwise.
+///
+/// For [`ColorMode::Gray4`], the image is dithered to 4 levels of gray and the planes are returned
+/// as `(lsb, msb)`, ready to be displayed as a [`Frame::Gray4`](crate::Frame::Gray4).
+///
+/// For [`ColorMode::BlackWhiteRed`], the image is dithered to black, white and red and the planes
+/// are returned as `(black, red)`, ready to be displayed as a
+/// [`Frame::BlackWhiteRed`](crate::Frame::BlackWhiteRed).
+///
+/// # Examples
+///
+/// ```no_run
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd, Frame};
+/// use waveshare_rpi::converter::{ColorMode, EpdImageOptions, image_to_epd_planes};
+///
+/// let mut options = EpdImageOptions::new();
+/// options.color_mode = ColorMode::Gray4;
+/// options.load_epd_config(EPD_CONFIG);
+/// let (lsb, msb) = image_to_epd_planes("photo.jpg", options).unwrap();
+///
+/// let mut epd = Epd::new(EPD_CONFIG).unwrap();
+/// epd.display(Frame::Gray4 { lsb: &lsb, msb: &msb }).unwrap();
+/// ```
+pub fn image_to_epd_planes(
+    filepath: &str,
+    options: EpdImageOptions,
+) -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+    match options.color_mode {
+        ColorMode::Gray4 => {
+            let mut img = DynamicImage::ImageRgb8(load_image(filepath, &options)?).into_luma8();
+            dither(&mut img, &Gray4Map);
+            let pixels = img.into_raw();
+            let mut lsb = vec![0; pixels.len() / 8];
+            let mut msb = vec![0; pixels.len() / 8];
+            for (i, &pixel) in pixels.iter().enumerate() {
+                let darkness = 3 - Gray4Map.index_of(&Luma([pixel]));
+                if darkness & 1 != 0 {
+                    lsb[i / 8] |= 1 << (7 - i % 8);
+                }
+                if darkness & 2 != 0 {
+                    msb[i / 8] |= 1 << (7 - i % 8);
+                }
+            }
+            Ok((lsb, msb))
+        }
+        ColorMode::BlackWhiteRed => {
+            let mut img = load_image(filepath, &options)?;
+            dither(&mut img, &BlackWhiteRedMap);
+            let mut black = vec![0; (img.width() * img.height()) as usize / 8];
+            let mut red = black.clone();
+            for (i, pixel) in img.pixels().enumerate() {
+                match BlackWhiteRedMap.index_of(pixel) {
+                    0 => black[i / 8] |= 1 << (7 - i % 8),
+                    2 => red[i / 8] |= 1 << (7 - i % 8),
+                    _ => {}
+                }
+            }
+            Ok((black, red))
+        }
+        ColorMode::BlackWhite => Err("color mode is not supported by image_to_epd_planes".into()),
+    }
+}
+
 /// Convert text to EPD format to be displayed on the e-paper display. Uses the [`ril`]
 /// as the backend to render text.
 ///
@@ -1,16 +1,42 @@
-use crate::epd_configs::{Action, EpdConfig};
+use crate::epd_configs::{
+    Action, BusyPolarity, Controller, EpdConfig, PanelColors, POWER_OFF_COMMANDS,
+    POWER_ON_COMMANDS, REFRESH_COMMANDS,
+};
+use std::borrow::Cow;
 
 pub const EPD_CONFIG: EpdConfig = EpdConfig {
-    init_commands: &[
+    init_commands: Cow::Borrowed(&[
         Action::SendCommand(0x04),
         Action::ReadBusy,
         Action::SendCommand(0x00),
-        Action::SendData(&[0x1f]),
+        Action::SendData(Cow::Borrowed(&[0x1f])),
         Action::SendCommand(0x61),
-        Action::SendData(&[0x08, 0x01, 0x28]),
+        // 128x296 is sent as [0x80, 0x01, 0x28], like Waveshare's driver does
+        Action::SendResolution,
+        Action::SendCommand(0x50),
+        Action::SendData(Cow::Borrowed(&[0x97])),
+    ]),
+    fast_init_commands: None,
+    partial_init_commands: None,
+    gray4_init_commands: None,
+    refresh_commands: Cow::Borrowed(REFRESH_COMMANDS),
+    power_on_commands: Cow::Borrowed(POWER_ON_COMMANDS),
+    power_off_commands: Cow::Borrowed(POWER_OFF_COMMANDS),
+    // Floats the border while powering off so it keeps its color during sleep
+    sleep_commands: Cow::Borrowed(&[
         Action::SendCommand(0x50),
-        Action::SendData(&[0x97]),
-    ],
+        Action::SendData(Cow::Borrowed(&[0xf7])),
+        Action::SendCommand(0x02),
+        Action::ReadBusy,
+        Action::SendCommand(0x07),
+        Action::SendData(Cow::Borrowed(&[0xA5])),
+        Action::Delay(1500),
+    ]),
+    busy_command: Some(0x71),
+    busy_polarity: BusyPolarity::ActiveLow,
+    controller: Controller::Uc8151,
+    inverted: true,
     width: 128,
     height: 296,
+    colors: PanelColors::BlackWhite,
 };
@@ -0,0 +1,43 @@
+use crate::epd_configs::{
+    Action, BusyPolarity, Controller, EpdConfig, PanelColors, POWER_OFF_COMMANDS,
+    POWER_ON_COMMANDS, REFRESH_COMMANDS, SLEEP_COMMANDS,
+};
+use std::borrow::Cow;
+
+pub const EPD_CONFIG: EpdConfig = EpdConfig {
+    init_commands: Cow::Borrowed(&[
+        Action::SendCommand(0x01),
+        Action::SendData(Cow::Borrowed(&[0x07, 0x07, 0x3f, 0x3f])),
+        Action::SendCommand(0x06),
+        Action::SendData(Cow::Borrowed(&[0x17, 0x17, 0x28, 0x17])),
+        Action::SendCommand(0x04),
+        Action::Delay(100),
+        Action::ReadBusy,
+        Action::SendCommand(0x00),
+        Action::SendData(Cow::Borrowed(&[0x0f])),
+        Action::SendCommand(0x61),
+        Action::SendResolution,
+        Action::SendCommand(0x15),
+        Action::SendData(Cow::Borrowed(&[0x00])),
+        Action::SendCommand(0x50),
+        Action::SendData(Cow::Borrowed(&[0x11, 0x07])),
+        Action::SendCommand(0x60),
+        Action::SendData(Cow::Borrowed(&[0x22])),
+        Action::SendCommand(0x65),
+        Action::SendData(Cow::Borrowed(&[0x00, 0x00, 0x00, 0x00])),
+    ]),
+    fast_init_commands: None,
+    partial_init_commands: None,
+    gray4_init_commands: None,
+    refresh_commands: Cow::Borrowed(REFRESH_COMMANDS),
+    power_on_commands: Cow::Borrowed(POWER_ON_COMMANDS),
+    power_off_commands: Cow::Borrowed(POWER_OFF_COMMANDS),
+    sleep_commands: Cow::Borrowed(SLEEP_COMMANDS),
+    busy_command: Some(0x71),
+    busy_polarity: BusyPolarity::ActiveLow,
+    controller: Controller::Uc8179,
+    inverted: true,
+    width: 800,
+    height: 480,
+    colors: PanelColors::BlackWhiteRed,
+};
@@ -1,21 +1,282 @@
 //! Contains the configurations for different Waveshare e-ink display models.
+//!
+//! Besides the initialization sequences, each model describes how to refresh, power on, power off
+//! and put its controller to sleep, and how it signals that it is busy. The opcodes of the data RAMs
+//! (`0x10`, `0x13`) and of the partial window (`0x90`, `0x91`, `0x92`) are not part of the config
+//! and are sent by [`Epd`](crate::Epd) directly, so only displays with an UltraChip UC81xx
+//! controller are supported, such as the UC8179 (7.5" and 5.83") and the UC8151 (2.9").
+//!
+//! Models which are not built into the crate can be described by constructing an [`EpdConfig`],
+//! or with the `serde` feature, by loading one from a TOML or JSON file at runtime:
+//!
+//! ```toml
+//! width = 128
+//! height = 296
+//! inverted = true
+//! controller = "uc8151"
+//! init_commands = [
+//!     { hardware_reset = { before_ms = 10, low_ms = 10, after_ms = 10 } },
+//!     { send_command = 0x04 },
+//!     "read_busy",
+//!     { send_command = 0x00 },
+//!     { send_data = [0x1f] },
+//!     { send_command = 0x61 },
+//!     "send_resolution",
+//!     { send_command = 0x50 },
+//!     { send_data = [0x97] },
+//! ]
+//! ```
+//!
+//! Fields which are left out take their values from [`EpdConfig::default`], which uses the
+//! sequences of the UC81xx controllers.
+pub mod epd2in9d;
+pub mod epd5in83_v2;
 pub mod epd7in5_v2;
+pub mod epd7in5b_v2;
+
+use crate::EpdError;
+use std::borrow::Cow;
+#[cfg(feature = "serde")]
+use std::path::Path;
 
 /// Represents the configuration of a Waveshare e-ink display model.
-/// 
-/// Todo: Add partial refresh and more available color modes.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct EpdConfig {
-    pub(crate) init_commands: &'static [Action],
+    /// Commands to initialize the display for [`RefreshMode::Normal`](crate::RefreshMode::Normal)
+    /// after a hardware reset.
+    pub init_commands: Cow<'static, [Action]>,
+    /// Commands to initialize the display for [`RefreshMode::Fast`](crate::RefreshMode::Fast),
+    /// `None` if unsupported. Models with waveform LUTs in registers load them here.
+    pub fast_init_commands: Option<Cow<'static, [Action]>>,
+    /// Commands to initialize the display for partial refreshes, `None` if unsupported.
+    pub partial_init_commands: Option<Cow<'static, [Action]>>,
+    /// Commands to initialize the display for 4-level grayscale, `None` if unsupported.
+    pub gray4_init_commands: Option<Cow<'static, [Action]>>,
+    /// Commands to refresh the display with the contents of the data RAMs and wait for it.
+    pub refresh_commands: Cow<'static, [Action]>,
+    /// Commands to power the display back on after it was powered off.
+    pub power_on_commands: Cow<'static, [Action]>,
+    /// Commands to power off the display while keeping its settings.
+    pub power_off_commands: Cow<'static, [Action]>,
+    /// Commands to power off the display and put it into deep sleep.
+    pub sleep_commands: Cow<'static, [Action]>,
+    /// Command to send before polling the busy pin, if the controller needs one.
+    pub busy_command: Option<u8>,
+    pub busy_polarity: BusyPolarity,
+    pub controller: Controller,
+    /// Whether set bits in the black/white data RAM are white rather than black. Each init
+    /// sequence which writes the VCOM and data interval setting (`0x50`) selects the polarity with
+    /// its data polarity bit (DDX\[0\]) instead, so this only applies to the others.
+    pub inverted: bool,
     pub width: usize,
     pub height: usize,
+    pub colors: PanelColors,
+}
+
+impl Default for EpdConfig {
+    fn default() -> Self {
+        Self {
+            init_commands: Cow::Borrowed(&[]),
+            fast_init_commands: None,
+            partial_init_commands: None,
+            gray4_init_commands: None,
+            refresh_commands: Cow::Borrowed(REFRESH_COMMANDS),
+            power_on_commands: Cow::Borrowed(POWER_ON_COMMANDS),
+            power_off_commands: Cow::Borrowed(POWER_OFF_COMMANDS),
+            sleep_commands: Cow::Borrowed(SLEEP_COMMANDS),
+            busy_command: Some(0x71),
+            busy_polarity: BusyPolarity::ActiveLow,
+            controller: Controller::Uc8179,
+            inverted: false,
+            width: 0,
+            height: 0,
+            colors: PanelColors::BlackWhite,
+        }
+    }
+}
+
+impl EpdConfig {
+    /// Checks that the dimensions of the display can be driven by [`Epd`](crate::Epd).
+    /// Returns `Err(EpdError::InvalidConfig)` if they cannot.
+    pub fn validate(&self) -> Result<(), EpdError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(EpdError::InvalidConfig(
+                "width and height must not be 0".into(),
+            ));
+        }
+        if !self.width.is_multiple_of(8) {
+            return Err(EpdError::InvalidConfig(
+                "width must be a multiple of 8".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parses and validates a config in TOML format.
+    #[cfg(feature = "serde")]
+    pub fn from_toml(s: &str) -> Result<Self, EpdError> {
+        let config: Self = toml::from_str(s).map_err(|e| EpdError::InvalidConfig(Box::new(e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses and validates a config in JSON format.
+    #[cfg(feature = "serde")]
+    pub fn from_json(s: &str) -> Result<Self, EpdError> {
+        let config: Self =
+            serde_json::from_str(s).map_err(|e| EpdError::InvalidConfig(Box::new(e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads a config from a `.toml` or `.json` file, depending on the extension of `path`.
+    #[cfg(feature = "serde")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, EpdError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(|e| EpdError::InvalidConfig(Box::new(e)))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&s),
+            Some("json") => Self::from_json(&s),
+            _ => Err(EpdError::InvalidConfig(
+                format!("{} is not a .toml or .json file", path.display()).into(),
+            )),
+        }
+    }
+}
+
+/// Colors which a display model is able to show.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum PanelColors {
+    /// Black and white displays.
+    #[default]
+    BlackWhite,
+    /// Black, white and red displays.
+    BlackWhiteRed,
+}
+
+/// Level of the BUSY pin while the display is busy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum BusyPolarity {
+    /// The BUSY pin is low while the display is busy, as on the UC81xx controllers.
+    #[default]
+    ActiveLow,
+    /// The BUSY pin is high while the display is busy.
+    ActiveHigh,
+}
+
+/// Display controller chip of a display model, which determines how values such as the
+/// resolution are encoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Controller {
+    /// UltraChip UC8179, used by the 7.5" and 5.83" displays.
+    #[default]
+    Uc8179,
+    /// UltraChip UC8151, used by the 2.9" flexible display.
+    Uc8151,
+}
+
+impl Controller {
+    /// Encodes the data of the resolution setting command (`0x61`).
+    pub(crate) fn resolution(&self, width: usize, height: usize) -> Vec<u8> {
+        match self {
+            Controller::Uc8179 => vec![
+                (width >> 8) as u8,
+                width as u8,
+                (height >> 8) as u8,
+                height as u8,
+            ],
+            // The horizontal resolution only has a byte, in multiples of 8
+            Controller::Uc8151 => vec![(width & 0xF8) as u8, (height >> 8) as u8, height as u8],
+        }
+    }
+
+    /// Decodes the two bytes read after the temperature sensor command (`0x40`) into °C. The
+    /// first byte holds the whole degrees in two's complement and the top bits of the second
+    /// byte hold the fraction.
+    pub(crate) fn temperature(&self, data: [u8; 2]) -> f32 {
+        let whole = data[0] as i8 as f32;
+        match self {
+            Controller::Uc8179 => whole + (data[1] >> 5) as f32 * 0.125,
+            Controller::Uc8151 => whole + (data[1] >> 7) as f32 * 0.5,
+        }
+    }
 }
 
 /// Possible actions to execute to the Waveshare E-Paper Driver HAT.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Action {
+    /// Sends a command byte.
     SendCommand(u8),
-    SendData(&'static [u8]),
+    /// Sends data bytes for the last command.
+    SendData(Cow<'static, [u8]>),
+    /// Sends the busy command, if any, and waits until the display is no longer busy.
     ReadBusy,
+    /// Waits for the given number of milliseconds.
     Delay(u64),
+    /// Resets the display by holding RST high, low and high again for the given number of
+    /// milliseconds. Sequences which start with a reset are not preceded by the default reset.
+    HardwareReset {
+        before_ms: u64,
+        low_ms: u64,
+        after_ms: u64,
+    },
+    /// Drives the PWR pin high (`true`) or low (`false`).
+    SetPower(bool),
+    /// Waits until the BUSY pin is high (`true`) or low (`false`), without sending the busy
+    /// command. Gives up after the busy timeout like [`ReadBusy`](Action::ReadBusy).
+    WaitBusyPin(bool),
+    /// Runs the actions the given number of times.
+    Repeat {
+        count: usize,
+        actions: Cow<'static, [Action]>,
+    },
+    /// Sends the width and height of the display as data, encoded for its [`Controller`].
+    SendResolution,
 }
+
+/// Refreshes the display on the UC81xx controllers.
+pub(crate) const REFRESH_COMMANDS: &[Action] = &[
+    Action::SendCommand(0x12),
+    Action::Delay(100),
+    Action::ReadBusy,
+];
+
+/// Powers the display on on the UC81xx controllers.
+pub(crate) const POWER_ON_COMMANDS: &[Action] = &[Action::SendCommand(0x04), Action::ReadBusy];
+
+/// Powers the display off on the UC81xx controllers.
+pub(crate) const POWER_OFF_COMMANDS: &[Action] = &[Action::SendCommand(0x02), Action::ReadBusy];
+
+/// Powers the display off and puts it into deep sleep on the UC81xx controllers.
+pub(crate) const SLEEP_COMMANDS: &[Action] = &[
+    Action::SendCommand(0x02),
+    Action::ReadBusy,
+    Action::SendCommand(0x07),
+    Action::SendData(Cow::Borrowed(&[0xA5])),
+    Action::Delay(1500),
+];
@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error returned by the operations on an [`Epd`](crate::Epd) and its [`EpdInterface`](crate::interface::EpdInterface).
+#[derive(Debug)]
+pub enum EpdError {
+    /// The GPIO peripheral could not be accessed.
+    GpioUnavailable(Box<dyn Error + Send + Sync>),
+    /// The GPIO pin with the given BCM number, or the line with the given offset on its GPIO chip,
+    /// is already in use or does not exist.
+    PinBusy(u32),
+    /// The SPI bus could not be opened or written to.
+    Spi(Box<dyn Error + Send + Sync>),
+    /// The display did not become idle before the deadline.
+    BusyTimeout,
+    /// The display did not answer a read, so it is probably missing or damaged.
+    NoResponse,
+    /// The size of the image data does not match the EPD's config.
+    SizeMismatch { expected: usize, actual: usize },
+    /// The region does not fit on the display or is empty.
+    RegionOutOfBounds {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// The operation is not supported by the display or its interface.
+    Unsupported(&'static str),
+    /// The display config could not be loaded or does not describe a usable display.
+    InvalidConfig(Box<dyn Error + Send + Sync>),
+    /// A file could not be read or written.
+    Io(io::Error),
+}
+
+impl fmt::Display for EpdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpdError::GpioUnavailable(e) => write!(f, "GPIO is unavailable: {e}"),
+            EpdError::PinBusy(pin) => write!(f, "GPIO pin {pin} is in use or does not exist"),
+            EpdError::Spi(e) => write!(f, "SPI error: {e}"),
+            EpdError::BusyTimeout => write!(f, "timed out waiting for the EPD to become idle"),
+            EpdError::NoResponse => write!(f, "the EPD did not respond"),
+            EpdError::SizeMismatch { expected, actual } => write!(
+                f,
+                "image data is {actual} bytes but the EPD expects {expected} bytes"
+            ),
+            EpdError::RegionOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "{width}x{height} region at ({x}, {y}) does not fit on the EPD"
+            ),
+            EpdError::Unsupported(operation) => write!(f, "unsupported operation: {operation}"),
+            EpdError::InvalidConfig(e) => write!(f, "invalid EPD config: {e}"),
+            EpdError::Io(e) => write!(f, "I/O error: {e}"),
+        }
+    }
+}
+
+impl Error for EpdError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EpdError::GpioUnavailable(e) | EpdError::Spi(e) | EpdError::InvalidConfig(e) => {
+                Some(e.as_ref())
+            }
+            EpdError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rppal::gpio::Error> for EpdError {
+    fn from(e: rppal::gpio::Error) -> Self {
+        match e {
+            rppal::gpio::Error::PinUsed(pin) | rppal::gpio::Error::PinNotAvailable(pin) => {
+                EpdError::PinBusy(pin.into())
+            }
+            e => EpdError::GpioUnavailable(Box::new(e)),
+        }
+    }
+}
+
+impl From<rppal::spi::Error> for EpdError {
+    fn from(e: rppal::spi::Error) -> Self {
+        EpdError::Spi(Box::new(e))
+    }
+}
+
+#[cfg(feature = "linux")]
+impl From<gpio_cdev::Error> for EpdError {
+    fn from(e: gpio_cdev::Error) -> Self {
+        EpdError::GpioUnavailable(Box::new(e))
+    }
+}
+
+impl From<io::Error> for EpdError {
+    fn from(e: io::Error) -> Self {
+        EpdError::Io(e)
+    }
+}
@@ -0,0 +1,64 @@
+use std::borrow::Cow;
+
+/// Image data to be displayed on the EPD with [`Epd::display`](crate::Epd::display).
+///
+/// Every plane uses one bit per pixel and is drawn from left to right starting from the top-left,
+/// wrapping back to the left side of the next row when it reaches the right side of the current
+/// row. Byte slices convert into [`Frame::BlackWhite`], so plain image data can be passed to
+/// [`Epd::display`](crate::Epd::display) directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frame<'a> {
+    /// A black and white image. If a bit is set, the pixel will be black.
+    BlackWhite(&'a [u8]),
+    /// A 4-level grayscale image split into two bit-planes. The darkness of each pixel ranges from
+    /// 0 (white) to 3 (black) and is made up of its bit in `msb` and its bit in `lsb`.
+    Gray4 { lsb: &'a [u8], msb: &'a [u8] },
+    /// A black, white and red image split into a black and a red plane. If a bit is set in `red`,
+    /// the pixel will be red. Otherwise the pixel will be black if its bit is set in `black`.
+    BlackWhiteRed { black: &'a [u8], red: &'a [u8] },
+}
+
+impl<'a> Frame<'a> {
+    /// Returns the bit-planes of the frame.
+    pub(crate) fn planes(&self) -> Vec<&'a [u8]> {
+        match *self {
+            Frame::BlackWhite(data) => vec![data],
+            Frame::Gray4 { lsb, msb } => vec![lsb, msb],
+            Frame::BlackWhiteRed { black, red } => vec![black, red],
+        }
+    }
+
+    /// Returns a frame of the same kind with its planes replaced by `planes`, given in the order
+    /// returned by [`planes`](Frame::planes).
+    pub(crate) fn with_planes<'b>(&self, planes: &'b [Cow<'_, [u8]>]) -> Frame<'b> {
+        match *self {
+            Frame::BlackWhite(_) => Frame::BlackWhite(&planes[0]),
+            Frame::Gray4 { .. } => Frame::Gray4 {
+                lsb: &planes[0],
+                msb: &planes[1],
+            },
+            Frame::BlackWhiteRed { .. } => Frame::BlackWhiteRed {
+                black: &planes[0],
+                red: &planes[1],
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for Frame<'a> {
+    fn from(data: &'a [u8]) -> Self {
+        Frame::BlackWhite(data)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for Frame<'a> {
+    fn from(data: &'a Vec<u8>) -> Self {
+        Frame::BlackWhite(data)
+    }
+}
+
+impl<'a, const N: usize> From<&'a [u8; N]> for Frame<'a> {
+    fn from(data: &'a [u8; N]) -> Self {
+        Frame::BlackWhite(data)
+    }
+}
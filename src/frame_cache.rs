@@ -0,0 +1,266 @@
+use crate::epd_configs::{EpdConfig, PanelColors};
+use crate::{EpdError, Frame};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"EPDC";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 15;
+
+/// A copy of the image last shown on a display, in the layout of the display's data RAMs.
+///
+/// [`Epd`](crate::Epd) keeps the frame it last displayed, so it can skip frames which are already
+/// shown and find the region which changed for a partial refresh. The cache can be taken out with
+/// [`Epd::frame_cache`](crate::Epd::frame_cache), saved to a file and handed to the `Epd` of a
+/// later run with [`Epd::set_frame_cache`](crate::Epd::set_frame_cache).
+///
+/// # Examples
+///
+/// ```no_run
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd, FrameCache};
+///
+/// # fn main() -> Result<(), waveshare_rpi::EpdError> {
+/// let mut epd = Epd::new(EPD_CONFIG)?;
+/// if let Ok(cache) = FrameCache::load("/var/cache/epd.bin") {
+///     epd.set_frame_cache(cache);
+/// }
+/// let data = vec![0x00; epd.image_buffer_size()];
+/// epd.display(&data)?;
+/// if let Some(cache) = epd.frame_cache() {
+///     cache.save("/var/cache/epd.bin")?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameCache {
+    width: usize,
+    height: usize,
+    /// The flips done by the controller's scan direction when the frame was shown.
+    scan_flips: (bool, bool),
+    kind: FrameKind,
+    planes: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    BlackWhite,
+    Gray4,
+    BlackWhiteRed,
+    /// Only the black plane is known, because parts of the display were updated in another mode
+    /// or were never written.
+    Approximate,
+}
+
+impl FrameKind {
+    fn planes(self) -> usize {
+        match self {
+            FrameKind::BlackWhite | FrameKind::Approximate => 1,
+            FrameKind::Gray4 | FrameKind::BlackWhiteRed => 2,
+        }
+    }
+}
+
+impl FrameCache {
+    /// Creates the cache of `frame`, which is in the layout of the data RAMs, shown on a display
+    /// described by `config`.
+    pub(crate) fn new(config: &EpdConfig, scan_flips: (bool, bool), frame: &Frame) -> Self {
+        let (kind, planes) = match (*frame, config.colors) {
+            // Tri-color panels always get a red plane
+            (Frame::BlackWhite(black), PanelColors::BlackWhiteRed) => (
+                FrameKind::BlackWhiteRed,
+                vec![black.to_vec(), vec![0x00; black.len()]],
+            ),
+            (Frame::BlackWhite(data), PanelColors::BlackWhite) => {
+                (FrameKind::BlackWhite, vec![data.to_vec()])
+            }
+            (Frame::Gray4 { .. }, _) => (FrameKind::Gray4, Self::owned_planes(frame)),
+            (Frame::BlackWhiteRed { .. }, _) => {
+                (FrameKind::BlackWhiteRed, Self::owned_planes(frame))
+            }
+        };
+        Self {
+            width: config.width,
+            height: config.height,
+            scan_flips,
+            kind,
+            planes,
+        }
+    }
+
+    fn owned_planes(frame: &Frame) -> Vec<Vec<u8>> {
+        frame.planes().into_iter().map(<[u8]>::to_vec).collect()
+    }
+
+    /// Creates a cache which only approximates the display with a black and white image.
+    pub(crate) fn approximate(
+        config: &EpdConfig,
+        scan_flips: (bool, bool),
+        black: Vec<u8>,
+    ) -> Self {
+        Self {
+            width: config.width,
+            height: config.height,
+            scan_flips,
+            kind: FrameKind::Approximate,
+            planes: vec![black],
+        }
+    }
+
+    /// Returns whether the cache holds the exact image on the display.
+    pub(crate) fn is_exact(&self) -> bool {
+        self.kind != FrameKind::Approximate
+    }
+
+    /// Returns the frame held by the cache, or `None` if it is not exact.
+    pub(crate) fn frame(&self) -> Option<Frame<'_>> {
+        match self.kind {
+            FrameKind::BlackWhite => Some(Frame::BlackWhite(&self.planes[0])),
+            FrameKind::Gray4 => Some(Frame::Gray4 {
+                lsb: &self.planes[0],
+                msb: &self.planes[1],
+            }),
+            FrameKind::BlackWhiteRed => Some(Frame::BlackWhiteRed {
+                black: &self.planes[0],
+                red: &self.planes[1],
+            }),
+            FrameKind::Approximate => None,
+        }
+    }
+
+    /// Returns the black and white approximation of the frame, where set bits are black.
+    pub(crate) fn black(&self) -> &[u8] {
+        match self.kind {
+            // Partial refreshes turn the darker half of the gray levels black
+            FrameKind::Gray4 => &self.planes[1],
+            _ => &self.planes[0],
+        }
+    }
+
+    /// Returns the cache after the black and white image `black` has been written over parts of
+    /// the frame.
+    pub(crate) fn with_black(&self, black: Vec<u8>) -> Self {
+        let kind = match self.kind {
+            FrameKind::BlackWhite => FrameKind::BlackWhite,
+            _ => FrameKind::Approximate,
+        };
+        Self {
+            kind,
+            planes: vec![black],
+            ..*self
+        }
+    }
+
+    /// Returns whether the cache was made for a display of the same size and scan direction.
+    pub(crate) fn fits(&self, config: &EpdConfig, scan_flips: (bool, bool)) -> bool {
+        self.width == config.width && self.height == config.height && self.scan_flips == scan_flips
+    }
+
+    /// Returns whether showing `frame` would leave the display unchanged.
+    pub(crate) fn shows(&self, frame: &FrameCache) -> bool {
+        self.is_exact() && self == frame
+    }
+
+    /// Returns the smallest region, widened to whole bytes, which holds all pixels that differ
+    /// between two black and white frames as `(x, y, width, height)`. Returns `None` if either
+    /// frame is not black and white or they are identical.
+    pub(crate) fn changed_region(
+        &self,
+        frame: &FrameCache,
+    ) -> Option<(usize, usize, usize, usize)> {
+        if self.kind != FrameKind::BlackWhite || frame.kind != FrameKind::BlackWhite {
+            return None;
+        }
+        let row_bytes = self.width / 8;
+        let (mut first_col, mut last_col) = (usize::MAX, 0);
+        let (mut first_row, mut last_row) = (usize::MAX, 0);
+        for (i, (old, new)) in self.planes[0].iter().zip(&frame.planes[0]).enumerate() {
+            if old != new {
+                let (row, col) = (i / row_bytes, i % row_bytes);
+                first_col = first_col.min(col);
+                last_col = last_col.max(col);
+                first_row = first_row.min(row);
+                last_row = last_row.max(row);
+            }
+        }
+        (first_row != usize::MAX).then(|| {
+            (
+                first_col * 8,
+                first_row,
+                (last_col - first_col + 1) * 8,
+                last_row - first_row + 1,
+            )
+        })
+    }
+
+    /// Returns the number of pixels of the display.
+    pub(crate) fn area(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Writes the cache to the file at `path`, replacing it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EpdError> {
+        Ok(fs::write(path, self.to_bytes())?)
+    }
+
+    /// Reads a cache written by [`save`](FrameCache::save) from the file at `path`.
+    /// Returns `Err(EpdError::Io)` if the file cannot be read or does not hold a frame cache.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EpdError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_SIZE + self.planes.iter().map(Vec::len).sum::<usize>());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(match self.kind {
+            FrameKind::BlackWhite => 0,
+            FrameKind::Gray4 => 1,
+            FrameKind::BlackWhiteRed => 2,
+            FrameKind::Approximate => 3,
+        });
+        bytes.push(u8::from(self.scan_flips.0) | u8::from(self.scan_flips.1) << 1);
+        bytes.extend_from_slice(&(self.width as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as u32).to_le_bytes());
+        for plane in &self.planes {
+            bytes.extend_from_slice(plane);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, EpdError> {
+        let invalid = || {
+            EpdError::Io(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a frame cache",
+            ))
+        };
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return Err(invalid());
+        }
+        let kind = match bytes[5] {
+            0 => FrameKind::BlackWhite,
+            1 => FrameKind::Gray4,
+            2 => FrameKind::BlackWhiteRed,
+            3 => FrameKind::Approximate,
+            _ => return Err(invalid()),
+        };
+        let scan_flips = (bytes[6] & 0x01 != 0, bytes[6] & 0x02 != 0);
+        let width = u32::from_le_bytes(bytes[7..11].try_into().unwrap()) as usize;
+        let height = u32::from_le_bytes(bytes[11..15].try_into().unwrap()) as usize;
+        let plane_size = width / 8 * height;
+        let data = &bytes[HEADER_SIZE..];
+        if plane_size == 0 || data.len() != plane_size * kind.planes() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            width,
+            height,
+            scan_flips,
+            kind,
+            planes: data.chunks(plane_size).map(<[u8]>::to_vec).collect(),
+        })
+    }
+}
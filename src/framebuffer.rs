@@ -0,0 +1,225 @@
+//! Contains a framebuffer which can be drawn on with [`embedded-graphics`](https://docs.rs/embedded-graphics).
+//!
+//! This module is only available with the `graphics` feature, which is enabled by default.
+use crate::epd_configs::{EpdConfig, PanelColors};
+use crate::{Frame, Rotation};
+use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2, GrayColor};
+use embedded_graphics_core::prelude::*;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+
+/// An in-memory image in the format of the EPD which implements [`DrawTarget`].
+///
+/// With [`BinaryColor`] every pixel is either black ([`BinaryColor::On`]) or white
+/// ([`BinaryColor::Off`]). With [`Gray2`] the meaning of the four levels depends on the colors of
+/// the panel the framebuffer was created for:
+///
+/// | Level | Black/white panel | Black/white/red panel |
+/// |---|---|---|
+/// | 0 | Black | Black |
+/// | 1 | Dark gray | Red |
+/// | 2 | Light gray | Red |
+/// | 3 | White | White |
+///
+/// A reference to the framebuffer converts into a [`Frame`], so it can be passed to
+/// [`Epd::display`](crate::Epd::display) directly.
+///
+/// # Examples
+///
+/// ```
+/// use embedded_graphics_core::{pixelcolor::BinaryColor, prelude::*, primitives::Rectangle};
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+/// use waveshare_rpi::framebuffer::EpdFrameBuffer;
+/// use waveshare_rpi::simulator::EpdSimulator;
+///
+/// let mut fb = EpdFrameBuffer::<BinaryColor>::new(&EPD_CONFIG);
+/// fb.fill_solid(&Rectangle::new(Point::new(8, 8), Size::new(16, 16)), BinaryColor::On)
+///     .unwrap();
+///
+/// let mut epd = Epd::with_interface(EPD_CONFIG, EpdSimulator::new(&EPD_CONFIG)).unwrap();
+/// epd.display(&fb).unwrap();
+/// assert_eq!(epd.interface().visible_image().get_pixel(8, 8).0, [0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpdFrameBuffer<C = BinaryColor> {
+    width: usize,
+    height: usize,
+    colors: PanelColors,
+    first: Vec<u8>,
+    second: Vec<u8>,
+    color: PhantomData<C>,
+}
+
+impl<C> EpdFrameBuffer<C> {
+    fn with_planes(config: &EpdConfig, rotation: Rotation, planes: usize) -> Self {
+        let (width, height) = match rotation {
+            Rotation::Rotate0 | Rotation::Rotate180 => (config.width, config.height),
+            Rotation::Rotate90 | Rotation::Rotate270 => (config.height, config.width),
+        };
+        let buffer_size = width.div_ceil(8) * height;
+        Self {
+            width,
+            height,
+            colors: config.colors,
+            first: vec![0x00; buffer_size],
+            second: vec![0x00; if planes > 1 { buffer_size } else { 0 }],
+            color: PhantomData,
+        }
+    }
+
+    /// Returns the width of the framebuffer in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height of the framebuffer in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the byte index and bit mask of the pixel at `point`, or `None` if it is outside
+    /// the framebuffer.
+    fn locate(&self, point: Point) -> Option<(usize, u8)> {
+        let x = usize::try_from(point.x).ok().filter(|&x| x < self.width)?;
+        let y = usize::try_from(point.y).ok().filter(|&y| y < self.height)?;
+        Some((y * self.width.div_ceil(8) + x / 8, 0x80 >> (x % 8)))
+    }
+}
+
+fn set_bit(plane: &mut [u8], index: usize, mask: u8, set: bool) {
+    if set {
+        plane[index] |= mask;
+    } else {
+        plane[index] &= !mask;
+    }
+}
+
+impl EpdFrameBuffer<BinaryColor> {
+    /// Creates a white black and white framebuffer with the dimensions described by `config`.
+    pub fn new(config: &EpdConfig) -> Self {
+        Self::with_planes(config, Rotation::Rotate0, 1)
+    }
+
+    /// Creates a white black and white framebuffer for a display rotated by `rotation` with
+    /// [`Epd::set_rotation`](crate::Epd::set_rotation).
+    pub fn with_rotation(config: &EpdConfig, rotation: Rotation) -> Self {
+        Self::with_planes(config, rotation, 1)
+    }
+}
+
+impl EpdFrameBuffer<Gray2> {
+    /// Creates a white 2-bit framebuffer with the dimensions and colors described by `config`.
+    pub fn new(config: &EpdConfig) -> Self {
+        Self::with_rotation(config, Rotation::Rotate0)
+    }
+
+    /// Creates a white 2-bit framebuffer for a display rotated by `rotation` with
+    /// [`Epd::set_rotation`](crate::Epd::set_rotation).
+    pub fn with_rotation(config: &EpdConfig, rotation: Rotation) -> Self {
+        let mut fb = Self::with_planes(config, rotation, 2);
+        fb.fill(Gray2::WHITE);
+        fb
+    }
+
+    fn fill(&mut self, color: Gray2) {
+        let (first, second) = self.plane_bits(color);
+        self.first.fill(if first { 0xFF } else { 0x00 });
+        self.second.fill(if second { 0xFF } else { 0x00 });
+    }
+
+    /// Returns the bits of `color` in the first and second plane.
+    fn plane_bits(&self, color: Gray2) -> (bool, bool) {
+        match self.colors {
+            // Black plane and red plane
+            PanelColors::BlackWhiteRed => match color.luma() {
+                0 => (true, false),
+                3 => (false, false),
+                _ => (false, true),
+            },
+            // Least and most significant bit of the darkness
+            PanelColors::BlackWhite => {
+                let darkness = 3 - color.luma();
+                (darkness & 0x01 != 0, darkness & 0x02 != 0)
+            }
+        }
+    }
+}
+
+impl OriginDimensions for EpdFrameBuffer<BinaryColor> {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl OriginDimensions for EpdFrameBuffer<Gray2> {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for EpdFrameBuffer<BinaryColor> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some((index, mask)) = self.locate(point) {
+                set_bit(&mut self.first, index, mask, color.is_on());
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.first.fill(if color.is_on() { 0xFF } else { 0x00 });
+        Ok(())
+    }
+}
+
+impl DrawTarget for EpdFrameBuffer<Gray2> {
+    type Color = Gray2;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if let Some((index, mask)) = self.locate(point) {
+                let (first, second) = self.plane_bits(color);
+                set_bit(&mut self.first, index, mask, first);
+                set_bit(&mut self.second, index, mask, second);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill(color);
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a EpdFrameBuffer<BinaryColor>> for Frame<'a> {
+    fn from(fb: &'a EpdFrameBuffer<BinaryColor>) -> Self {
+        Frame::BlackWhite(&fb.first)
+    }
+}
+
+impl<'a> From<&'a EpdFrameBuffer<Gray2>> for Frame<'a> {
+    fn from(fb: &'a EpdFrameBuffer<Gray2>) -> Self {
+        match fb.colors {
+            PanelColors::BlackWhiteRed => Frame::BlackWhiteRed {
+                black: &fb.first,
+                red: &fb.second,
+            },
+            PanelColors::BlackWhite => Frame::Gray4 {
+                lsb: &fb.first,
+                msb: &fb.second,
+            },
+        }
+    }
+}
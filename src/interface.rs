@@ -0,0 +1,49 @@
+//! Contains the hardware transport used by [`Epd`](crate::Epd) to talk to the e-paper driver board.
+use crate::EpdError;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Low-level transport between the host and the e-paper driver board.
+///
+/// [`Epd`](crate::Epd) only ever talks to the display through this trait, so other backends
+/// (another SBC, a simulator for testing, ...) can be plugged in by implementing it.
+/// [`RpiGpio`](crate::rpi_helper::RpiGpio) is the default implementation for the Raspberry Pi.
+pub trait EpdInterface {
+    /// Drives the RST pin high (`true`) or low (`false`).
+    fn set_rst(&mut self, high: bool) -> Result<(), EpdError>;
+
+    /// Drives the DC pin. Low selects a command byte, high selects data bytes.
+    fn set_dc(&mut self, high: bool) -> Result<(), EpdError>;
+
+    /// Drives the CS pin. The display only listens on the SPI bus while CS is low.
+    fn set_cs(&mut self, high: bool) -> Result<(), EpdError>;
+
+    /// Drives the PWR pin which powers the display on the newer driver HATs.
+    fn set_pwr(&mut self, high: bool) -> Result<(), EpdError>;
+
+    /// Writes the bytes out on the SPI bus.
+    fn spi_write(&mut self, data: &[u8]) -> Result<(), EpdError>;
+
+    /// Reads bytes sent by the display into `buffer`. The caller drives DC and CS around the read
+    /// like for [`spi_write`](EpdInterface::spi_write).
+    ///
+    /// The driver HATs only connect the display's bidirectional data line to MOSI, so not every
+    /// interface is able to read. The default implementation returns `Err(EpdError::Unsupported)`.
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<(), EpdError> {
+        let _ = buffer;
+        Err(EpdError::Unsupported("reading from the display"))
+    }
+
+    /// Returns `true` if the BUSY pin is currently high.
+    fn busy_high(&mut self) -> Result<bool, EpdError>;
+
+    /// Blocks until the BUSY pin changes level or `timeout` elapses, whichever comes first.
+    ///
+    /// The default implementation simply sleeps for `timeout`, which makes waiting on the display
+    /// a polling loop. Implementations supporting edge-triggered interrupts on the BUSY pin should
+    /// override it to return as soon as the level changes.
+    fn wait_for_busy_edge(&mut self, timeout: Duration) -> Result<(), EpdError> {
+        sleep(timeout);
+        Ok(())
+    }
+}
@@ -2,7 +2,16 @@
 This crate provides a high-level interface to the Waveshare e-paper displays for the Raspberry Pi. It is based on
 Waveshare's C library for the Raspberry Pi[^1] with some modifications to make it more idiomatic.
 `waveshare-rpi` also provides a [`converter`] module to convert images and text to the format for use
-with the e-paper displays.
+with the e-paper displays. The displays are driven through the [`interface::EpdInterface`] trait, which is
+implemented for the Raspberry Pi by [`rpi_helper::RpiGpio`] and can be implemented for other hosts.
+The [`simulator`] module provides an implementation which renders to an in-memory image for testing without
+any hardware attached. With the `graphics` feature, the [`framebuffer`] module provides a framebuffer which
+can be drawn on with [`embedded-graphics`](https://docs.rs/embedded-graphics). With the `async` feature, the
+[`async_epd`] module provides a variant of [`Epd`] for tokio which does not block the thread during refreshes.
+The [`recorder`] module records the traffic between [`Epd`] and the display and replays it on any interface.
+The [`registers`] module provides typed values for the border, VCOM and panel settings of the controllers.
+With the `linux` feature, the [`linux`] module provides an implementation for other Linux boards, built on
+`spidev` and the GPIO character device.
 
 If you intend to use it with other devices, or are unable to find your display model in the list below, please
 consider using the [`epd-waveshare`](https://docs.rs/epd-waveshare/latest/epd_waveshare/) crate instead, or open
@@ -15,6 +24,7 @@ a GitHub issue to request support for your display model.
 | [Waveshare 2.9inch flexible display](https://www.waveshare.com/2.9inch-e-paper-hat-d.htm) | No |
 | [Waveshare 5.83inch V2 display](https://www.waveshare.com/5.83inch-e-Paper-HAT.htm) | No |
 | [Waveshare 7.5inch V2 display](https://www.waveshare.com/7.5inch-e-Paper-HAT.htm) | Yes |
+| [Waveshare 7.5inch V2 black/white/red display](https://www.waveshare.com/7.5inch-e-paper-hat-b.htm) | No |
 
 # Example
 
@@ -25,7 +35,7 @@ use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
 use waveshare_rpi::converter::{ColorMode, EpdImageOptions, image_to_epd};
 
 // Initialize the interface to interact with the epd7in5_v2 display
-let mut my_epd = Epd::new(EPD_CONFIG);
+let mut my_epd = Epd::new(EPD_CONFIG).unwrap();
 
 // Initialize the image options for the image to be displayed on the display
 let mut image_options = EpdImageOptions::new();
@@ -41,160 +51,1776 @@ my_epd.display(&img_data).unwrap();
 [^1]: [https://github.com/waveshareteam/e-Paper](https://github.com/waveshareteam/e-Paper)
  */
 
+#[cfg(feature = "async")]
+pub mod async_epd;
 pub mod converter;
 pub mod epd_configs;
-mod rpi_helper;
+mod error;
+mod frame;
+mod frame_cache;
+#[cfg(feature = "graphics")]
+pub mod framebuffer;
+pub mod interface;
+#[cfg(feature = "linux")]
+pub mod linux;
+pub mod recorder;
+pub mod registers;
+pub mod rpi_helper;
+pub mod simulator;
+mod transform;
+mod wait;
 
-use epd_configs::{Action, EpdConfig};
-use rpi_helper::RpiGpio;
-use rppal::gpio::Level;
-use std::thread::sleep;
-use std::time::Duration;
+pub use error::EpdError;
+pub use frame::Frame;
+pub use frame_cache::FrameCache;
+
+use epd_configs::{Action, BusyPolarity, EpdConfig, PanelColors};
+use interface::EpdInterface;
+use recorder::{Event, Recorder};
+use registers::{BorderColor, PanelSetting, Registers, PANEL_SETTING, PSR_SHL, PSR_UD};
+use rpi_helper::{HardwareConfig, RpiGpio};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant};
+use transform::Transform;
+use wait::{block_on, Blocking, Wait};
 
 const DATA_BUFFER_SIZE: usize = 4096;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const FAST_MIN_TEMPERATURE: f32 = 10.0;
+/// How long to hold the RST pin high, low and high again in ms to reset the EPD, unless the init
+/// commands of the model start with their own [`Action::HardwareReset`].
+const RESET_TIMINGS: [u64; 3] = [20, 2, 20];
+/// Number of black and white cycles of [`Epd::deep_clean`].
+const DEEP_CLEAN_CYCLES: usize = 3;
+/// Time for the display's supply to settle after the PWR pin is driven high again, in ms.
+const PWR_ON_DELAY: u64 = 10;
+/// Longest time between two checks of the idle watchdog thread.
+const IDLE_WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lifecycle state of the display controller as tracked by [`Epd`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpdState {
+    /// The controller has not been initialized since it was last reset.
+    Uninitialized,
+    /// The controller is initialized and powered on, ready to display images.
+    Ready,
+    /// The controller is powered off (`0x02`) but keeps its settings.
+    PoweredOff,
+    /// The controller is in deep sleep (`0x07`) and ignores all commands until it is reset.
+    DeepSleep,
+    /// The PWR pin is low, so the display has no power and has to be re-initialized.
+    PowerCut,
+}
+
+/// Waveform used for full refreshes of the display.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RefreshMode {
+    /// The display's default waveform, which gives the best image quality but flashes the
+    /// display several times and takes a few seconds.
+    #[default]
+    Normal,
+    /// A shorter waveform for interactive screens, which updates in about 1.5 seconds at the cost
+    /// of more ghosting.
+    Fast,
+}
+
+/// Clockwise rotation of the images shown on the display.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+/// Source of the temperature used by [`TemperatureCompensation`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureSource {
+    /// The internal temperature sensor of the display controller, read before every full refresh.
+    Sensor,
+    /// A temperature in °C supplied by the user, e.g. from a sensor inside the enclosure. It is
+    /// also forced on the controller (`0xE5`), so normal refreshes use the waveform for it.
+    Fixed(f32),
+}
+
+/// Adjusts full refreshes to the temperature of the display.
+///
+/// The fast waveform leaves faint images with a lot of ghosting in the cold, so
+/// [`RefreshMode::Fast`] falls back to the normal waveform below `fast_min_celsius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureCompensation {
+    pub source: TemperatureSource,
+    /// Temperature in °C below which fast refreshes use the normal waveform. Defaults to 10 °C.
+    pub fast_min_celsius: f32,
+}
+
+impl TemperatureCompensation {
+    /// Compensates for the temperature read from the display's sensor.
+    pub fn sensor() -> Self {
+        Self {
+            source: TemperatureSource::Sensor,
+            fast_min_celsius: FAST_MIN_TEMPERATURE,
+        }
+    }
+
+    /// Compensates for a temperature in °C supplied by the user.
+    pub fn fixed(celsius: f32) -> Self {
+        Self {
+            source: TemperatureSource::Fixed(celsius),
+            fast_min_celsius: FAST_MIN_TEMPERATURE,
+        }
+    }
+}
+
+/// When to replace partial and fast refreshes with a normal full refresh to clear the ghosting
+/// they leave behind.
+///
+/// Every limit is disabled with `None`, which is the default. The limits are checked before each
+/// partial refresh, and before each full refresh in [`RefreshMode::Fast`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct GhostingPolicy {
+    /// Number of partial and fast refreshes after which the next one is a normal full refresh.
+    pub max_partial_refreshes: Option<u32>,
+    /// Time since the last normal full refresh after which the next refresh is a normal full
+    /// refresh.
+    pub max_age: Option<Duration>,
+    /// Largest fraction of the display (from 0.0 to 1.0) which may be covered by the bounding box
+    /// of the changes of a partial or fast refresh. Bigger changes get a normal full refresh.
+    pub max_changed_area: Option<f32>,
+}
+
+/// What an idle display is put into by [`IdlePolicy`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Powers off the display (`0x02`), which keeps its settings so it can be woken up quickly.
+    #[default]
+    PowerOff,
+    /// Puts the display into deep sleep (`0x07`), which draws the least current but has to be
+    /// re-initialized to wake up.
+    DeepSleep,
+}
+
+/// Powers down the display once it has not been used for a while. See [`Epd::set_idle_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdlePolicy {
+    /// How long the display has to be idle before it is powered down.
+    pub timeout: Duration,
+    pub action: IdleAction,
+    /// Whether to drive the PWR pin low after the action to cut the power of the display
+    /// completely.
+    pub cut_power: bool,
+}
+
+impl IdlePolicy {
+    /// Powers off the display once it has been idle for `timeout`.
+    pub fn power_off(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            action: IdleAction::PowerOff,
+            cut_power: false,
+        }
+    }
+
+    /// Puts the display into deep sleep once it has been idle for `timeout`.
+    pub fn deep_sleep(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            action: IdleAction::DeepSleep,
+            cut_power: false,
+        }
+    }
+}
+
+/// Flags read from the display controller with the get status command (`0x71`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    /// The controller is busy refreshing or powering on or off.
+    pub busy: bool,
+    /// The controller has finished powering off.
+    pub powered_off: bool,
+    /// The controller has finished powering on.
+    pub powered_on: bool,
+    /// The controller has received the image data for a refresh.
+    pub data_received: bool,
+    /// The controller is talking to an external temperature sensor over I²C.
+    pub i2c_busy: bool,
+    /// The last transfer with an external temperature sensor failed.
+    pub i2c_error: bool,
+    /// The controller is in partial mode (`0x91`).
+    pub partial: bool,
+}
+
+impl Status {
+    /// Decodes the status byte, whose bits are `BUSY_N`, `POF`, `PON`, `data_flag`,
+    /// `I2C_BUSYN`, `I2C_ERR` and `PTL_flag` from the least significant bit up.
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            busy: bits & 0x01 == 0,
+            powered_off: bits & 0x02 != 0,
+            powered_on: bits & 0x04 != 0,
+            data_received: bits & 0x08 != 0,
+            i2c_busy: bits & 0x10 == 0,
+            i2c_error: bits & 0x20 != 0,
+            partial: bits & 0x40 != 0,
+        }
+    }
+
+    /// Encodes the status into the byte sent by the controller.
+    pub fn bits(&self) -> u8 {
+        [
+            !self.busy,
+            self.powered_off,
+            self.powered_on,
+            self.data_received,
+            !self.i2c_busy,
+            self.i2c_error,
+            self.partial,
+        ]
+        .into_iter()
+        .enumerate()
+        .fold(0, |bits, (i, set)| bits | u8::from(set) << i)
+    }
+}
+
+/// Revision numbers read from the display controller with the revision command (`0x70`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Revision {
+    /// Revision of the product, 24 bits.
+    pub product: u32,
+    /// Revision of the waveform look-up tables in the OTP memory of the panel, 24 bits.
+    pub lut: u32,
+    /// Revision of the controller chip.
+    pub chip: u8,
+}
+
+impl Revision {
+    /// Decodes the seven bytes sent by the controller, with the 24-bit revisions in big-endian.
+    pub fn from_bytes(bytes: [u8; 7]) -> Self {
+        Self {
+            product: u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
+            lut: u32::from_be_bytes([0, bytes[3], bytes[4], bytes[5]]),
+            chip: bytes[6],
+        }
+    }
 
-/// Error returned when the size of the image data does not match the EPD's config.
-#[derive(Debug, Clone)]
-pub struct ImgSizeMismatchError;
+    /// Encodes the revision into the bytes sent by the controller.
+    pub fn to_bytes(&self) -> [u8; 7] {
+        let [_, p0, p1, p2] = self.product.to_be_bytes();
+        let [_, l0, l1, l2] = self.lut.to_be_bytes();
+        [p0, p1, p2, l0, l1, l2, self.chip]
+    }
+}
+
+/// Sequence of commands the controller was last initialized with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InitMode {
+    Full,
+    Fast,
+    Partial,
+    Gray4,
+}
 
 /// Represents a E-Paper Display.
-pub struct Epd {
+///
+/// `Epd` keeps track of the [`EpdState`] of the display, so the display is transparently woken up
+/// (and re-initialized if necessary) before the next [`display`](Epd::display) or
+/// [`clear`](Epd::clear) after it has been powered off or put to sleep.
+///
+/// The display is driven through an [`EpdInterface`], which defaults to the Raspberry Pi's GPIO
+/// and SPI peripherals ([`RpiGpio`]).
+pub struct Epd<I: EpdInterface = RpiGpio> {
     config: EpdConfig,
-    rpi: RpiGpio,
+    interface: I,
+    busy_timeout: Option<Duration>,
+    busy_poll_interval: Duration,
+    state: EpdState,
+    init_mode: InitMode,
+    /// Whether set bits in the black/white data RAM are white with the last init commands.
+    data_inverted: bool,
+    refresh_mode: RefreshMode,
+    /// The image last shown on the display, if known.
+    frame: Option<FrameCache>,
+    /// The largest fraction of the display which is updated with a partial refresh by
+    /// [`display`](Epd::display).
+    auto_partial: Option<f32>,
+    ghosting_policy: GhostingPolicy,
+    /// Number of partial and fast refreshes since the last normal full refresh.
+    ghosting_refreshes: u32,
+    /// Time of the last normal full refresh, or of the creation of the `Epd`.
+    last_clean: Instant,
+    temperature_compensation: Option<TemperatureCompensation>,
+    /// Whether the last temperature was too cold for fast refreshes.
+    cold: bool,
+    rotation: Rotation,
+    mirror_horizontal: bool,
+    mirror_vertical: bool,
+    /// Register values which replace those of the init commands.
+    registers: Registers,
+    idle_policy: Option<IdlePolicy>,
+    /// Time of the last command sent to the display.
+    last_activity: Instant,
+    recorder: Option<Recorder>,
 }
 
 impl Epd {
     /// Creates a new instance of `Epd` with the config of a Waveshare E-Paper Display.
-    pub fn new(config: EpdConfig) -> Self {
-        let rpi = RpiGpio::new();
-        let mut s = Self { config, rpi };
-        s.init();
-        s
+    pub fn new(config: EpdConfig) -> Result<Self, EpdError> {
+        Self::with_interface(config, RpiGpio::new()?)
+    }
+
+    /// Creates a new instance of `Epd` for a display wired up as described by `hardware_config`.
+    pub fn with_hardware_config(
+        config: EpdConfig,
+        hardware_config: HardwareConfig,
+    ) -> Result<Self, EpdError> {
+        Self::with_interface(config, RpiGpio::with_config(hardware_config)?)
+    }
+}
+
+impl<I: EpdInterface> Epd<I> {
+    /// Creates a new instance of `Epd` which talks to the display through `interface`.
+    pub fn with_interface(config: EpdConfig, interface: I) -> Result<Self, EpdError> {
+        let mut s = Self::uninitialized(config, interface);
+        s.init()?;
+        Ok(s)
+    }
+
+    fn uninitialized(config: EpdConfig, interface: I) -> Self {
+        Self {
+            config,
+            interface,
+            busy_timeout: Some(BUSY_TIMEOUT),
+            busy_poll_interval: BUSY_POLL_INTERVAL,
+            state: EpdState::Uninitialized,
+            init_mode: InitMode::Full,
+            data_inverted: false,
+            refresh_mode: RefreshMode::Normal,
+            frame: None,
+            auto_partial: None,
+            ghosting_policy: GhostingPolicy::default(),
+            ghosting_refreshes: 0,
+            last_clean: Instant::now(),
+            temperature_compensation: None,
+            cold: false,
+            rotation: Rotation::Rotate0,
+            mirror_horizontal: false,
+            mirror_vertical: false,
+            registers: Registers::default(),
+            idle_policy: None,
+            last_activity: Instant::now(),
+            recorder: None,
+        }
+    }
+
+    /// Returns a reference to the underlying hardware interface.
+    pub fn interface(&self) -> &I {
+        &self.interface
+    }
+
+    /// Returns a mutable reference to the underlying hardware interface.
+    pub fn interface_mut(&mut self) -> &mut I {
+        &mut self.interface
+    }
+
+    /// Sets how long to wait for the EPD to stop being busy before giving up with
+    /// [`EpdError::BusyTimeout`]. `None` waits forever. Defaults to 30 seconds.
+    pub fn set_busy_timeout(&mut self, timeout: Option<Duration>) {
+        self.busy_timeout = timeout;
+    }
+
+    /// Sets the interval at which the busy pin is re-read while waiting for the EPD, in case the
+    /// interface does not report a level change through an interrupt. Defaults to 100 ms.
+    pub fn set_busy_poll_interval(&mut self, interval: Duration) {
+        self.busy_poll_interval = interval;
     }
 
-    /// Sends commands to the EPD to initialize it.
-    pub fn init(&mut self) {
-        simple_logger::SimpleLogger::new().env().init().unwrap();
+    /// Returns the current lifecycle state of the display.
+    pub fn state(&self) -> EpdState {
+        self.state
+    }
+
+    /// Returns the refresh mode used by [`display`](Epd::display) and [`clear`](Epd::clear).
+    pub fn refresh_mode(&self) -> RefreshMode {
+        self.refresh_mode
+    }
+
+    /// Sets the refresh mode used by [`display`](Epd::display) and [`clear`](Epd::clear). The
+    /// display is re-initialized with the matching commands on the next refresh.
+    /// Returns `Err(EpdError::Unsupported)` if the display model does not support the mode.
+    pub fn set_refresh_mode(&mut self, mode: RefreshMode) -> Result<(), EpdError> {
+        if mode == RefreshMode::Fast && self.config.fast_init_commands.is_none() {
+            return Err(EpdError::Unsupported("fast refresh"));
+        }
+        self.refresh_mode = mode;
+        Ok(())
+    }
+
+    /// Lets [`display`](Epd::display) update only the changed part of a black and white image
+    /// with a partial refresh if the bounding box of the changes covers at most `max_area` (a
+    /// fraction from 0.0 to 1.0) of the display, or always do a full refresh with `None`. Disabled
+    /// by default.
+    /// Returns `Err(EpdError::Unsupported)` if the display model does not support partial
+    /// refreshes.
+    pub fn set_auto_partial(&mut self, max_area: Option<f32>) -> Result<(), EpdError> {
+        if max_area.is_some() && self.config.partial_init_commands.is_none() {
+            return Err(EpdError::Unsupported("partial refresh"));
+        }
+        self.auto_partial = max_area;
+        Ok(())
+    }
+
+    /// Sets when partial and fast refreshes are replaced with a normal full refresh to clear
+    /// ghosting. See [`GhostingPolicy`].
+    pub fn set_ghosting_policy(&mut self, policy: GhostingPolicy) {
+        self.ghosting_policy = policy;
+    }
+
+    /// Powers down the display according to `policy` once it has been idle for a while, or never
+    /// with `None`, which is the default. The display is woken up by the next operation.
+    ///
+    /// The policy is applied by [`poll_idle`](Epd::poll_idle), which has to be called
+    /// periodically, for example by the thread of [`watch_idle`](Epd::watch_idle).
+    pub fn set_idle_policy(&mut self, policy: Option<IdlePolicy>) {
+        self.idle_policy = policy;
+    }
+
+    /// Returns when the display will be due to be powered down by the idle policy, or `None` if
+    /// there is no idle policy or the display is already powered down.
+    pub fn idle_deadline(&self) -> Option<Instant> {
+        let policy = self.idle_policy?;
+        let powered_down = match self.state {
+            EpdState::PowerCut => true,
+            _ if policy.cut_power => false,
+            EpdState::DeepSleep => true,
+            // Powering off needs an initialized controller, which is left to the next operation
+            EpdState::PoweredOff | EpdState::Uninitialized => policy.action == IdleAction::PowerOff,
+            EpdState::Ready => false,
+        };
+        (!powered_down).then(|| self.last_activity + policy.timeout)
+    }
+
+    /// Powers down the display if it has been idle for longer than the timeout of the idle policy.
+    /// Returns whether the display was powered down.
+    pub fn poll_idle(&mut self) -> Result<bool, EpdError> {
+        block_on(self.poll_idle_with::<Blocking>())
+    }
+
+    async fn poll_idle_with<W: Wait>(&mut self) -> Result<bool, EpdError> {
+        let Some(policy) = self.idle_policy else {
+            return Ok(false);
+        };
+        if self
+            .idle_deadline()
+            .is_none_or(|deadline| Instant::now() < deadline)
+        {
+            return Ok(false);
+        }
+        log::info!("EPD has been idle for {:?}", policy.timeout);
+        match policy.action {
+            IdleAction::PowerOff => self.power_off_with::<W>().await?,
+            IdleAction::DeepSleep => self.sleep_with::<W>().await?,
+        }
+        if policy.cut_power {
+            self.cut_power()?;
+        }
+        Ok(true)
+    }
+
+    /// Writes every command, data payload, read, reset, busy wait and power change to `recorder`,
+    /// or stops recording with `None`, which is the default. See [`recorder`].
+    ///
+    /// The display is initialized when the `Epd` is created, so call [`init`](Epd::init) after
+    /// setting the recorder to include the initialization in the recording.
+    pub fn set_recorder(&mut self, recorder: Option<Recorder>) {
+        self.recorder = recorder;
+    }
+
+    /// Returns the time since the recorder was created, or `None` if nothing is recorded.
+    fn recording_time(&self) -> Option<Duration> {
+        self.recorder.as_ref().map(Recorder::elapsed)
+    }
+
+    /// Records `event` if there is a recorder.
+    fn record(&mut self, event: impl FnOnce() -> Event) {
+        self.record_at(self.recording_time(), event);
+    }
+
+    /// Records `event`, which started at `time`, if there is a recorder. A recorder which fails to
+    /// write is dropped, so the display keeps working.
+    fn record_at(&mut self, time: Option<Duration>, event: impl FnOnce() -> Event) {
+        let (Some(recorder), Some(time)) = (&mut self.recorder, time) else {
+            return;
+        };
+        if let Err(e) = recorder.record(time, event()) {
+            log::error!("Stopped recording after failing to write to the recording: {e}");
+            self.recorder = None;
+        }
+    }
+
+    fn set_pwr(&mut self, high: bool) -> Result<(), EpdError> {
+        self.record(|| Event::Power(high));
+        self.interface.set_pwr(high)
+    }
+
+    /// Drives the PWR pin low, which cuts the power of the display until the next operation.
+    pub fn cut_power(&mut self) -> Result<(), EpdError> {
+        log::info!("Cutting power of EPD");
+        self.set_pwr(false)?;
+        self.state = EpdState::PowerCut;
+        Ok(())
+    }
+
+    /// Returns whether the next partial or fast refresh should be a normal full refresh instead,
+    /// given the number of pixels covered by the bounding box of its changes if known.
+    fn clean_due(&self, changed_area: Option<usize>) -> bool {
+        let policy = self.ghosting_policy;
+        let area = (self.config.width * self.config.height) as f32;
+        policy
+            .max_partial_refreshes
+            .is_some_and(|max| self.ghosting_refreshes >= max)
+            || policy
+                .max_age
+                .is_some_and(|age| self.last_clean.elapsed() >= age)
+            || policy
+                .max_changed_area
+                .zip(changed_area)
+                .is_some_and(|(max, changed)| changed as f32 > max * area)
+    }
+
+    /// Returns `mode`, or the normal full refresh if the ghosting policy calls for one.
+    fn clean_mode(&self, mode: InitMode, changed_area: Option<usize>) -> InitMode {
+        if mode == InitMode::Fast && self.clean_due(changed_area) {
+            log::info!("Using a normal refresh to clear ghosting");
+            InitMode::Full
+        } else {
+            mode
+        }
+    }
+
+    /// Keeps count of the refreshes which leave ghosting behind.
+    fn record_refresh(&mut self) {
+        match self.init_mode {
+            InitMode::Full | InitMode::Gray4 => {
+                self.ghosting_refreshes = 0;
+                self.last_clean = Instant::now();
+            }
+            InitMode::Fast | InitMode::Partial => self.ghosting_refreshes += 1,
+        }
+    }
+
+    /// Returns the image last shown on the display through this `Epd` or handed over with
+    /// [`set_frame_cache`](Epd::set_frame_cache), or `None` if it is not known.
+    pub fn frame_cache(&self) -> Option<&FrameCache> {
+        self.frame.as_ref()
+    }
+
+    /// Tells the `Epd` which image is shown on the display, for example one saved with
+    /// [`FrameCache::save`] by an earlier run of the program. The next [`display`](Epd::display)
+    /// is skipped if it would show the same image. The cache is ignored if it was made for a
+    /// display of another size or orientation.
+    pub fn set_frame_cache(&mut self, cache: FrameCache) {
+        if cache.fits(&self.config, self.scan_flips()) {
+            self.frame = Some(cache);
+        } else {
+            log::warn!("Ignoring frame cache of another display size or orientation");
+        }
+    }
+
+    /// Resets the EPD and sends it the commands to initialize it for the current refresh mode.
+    pub fn init(&mut self) -> Result<(), EpdError> {
+        block_on(self.init_with::<Blocking>())
+    }
+
+    async fn init_with<W: Wait>(&mut self) -> Result<(), EpdError> {
         log::info!("Initializing display!");
-        self.reset();
-        for &command in self.config.init_commands {
-            match command {
+        self.load::<W>(self.full_init_mode()).await
+    }
+
+    fn full_init_mode(&self) -> InitMode {
+        match self.refresh_mode {
+            RefreshMode::Fast if !self.cold => InitMode::Fast,
+            _ => InitMode::Full,
+        }
+    }
+
+    /// Returns the clockwise rotation of the images shown on the display.
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Rotates all images passed to [`display`](Epd::display) and
+    /// [`display_region`](Epd::display_region) clockwise by `rotation`. With a rotation of 90 or
+    /// 270 degrees, images are [`height`](Epd::height) pixels wide and [`width`](Epd::width)
+    /// pixels high.
+    ///
+    /// 180 degrees and mirroring are done by the display controller by changing its scan
+    /// direction, while 90 and 270 degrees transpose the image data before it is sent. Parts of the
+    /// image which are not updated by a full refresh keep their previous orientation.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.set_orientation(rotation, self.mirror_horizontal, self.mirror_vertical);
+    }
+
+    /// Mirrors all images passed to [`display`](Epd::display) and
+    /// [`display_region`](Epd::display_region) horizontally (left to right) and/or vertically
+    /// (top to bottom). Images are mirrored before they are rotated.
+    pub fn set_mirroring(&mut self, horizontal: bool, vertical: bool) {
+        self.set_orientation(self.rotation, horizontal, vertical);
+    }
+
+    fn set_orientation(&mut self, rotation: Rotation, horizontal: bool, vertical: bool) {
+        let old_scan = self.scan_flips();
+        self.rotation = rotation;
+        self.mirror_horizontal = horizontal;
+        self.mirror_vertical = vertical;
+        if self.scan_flips() != old_scan {
+            // The scan direction is set while initializing, and the data RAM no longer matches
+            // the image on the display in the new direction
+            self.reinitialize_later();
+            self.frame = None;
+        }
+    }
+
+    /// Makes the next operation re-initialize the display, so the changed init commands take
+    /// effect. A display in deep sleep or without power is re-initialized anyway.
+    fn reinitialize_later(&mut self) {
+        if matches!(self.state, EpdState::Ready | EpdState::PoweredOff) {
+            self.state = EpdState::Uninitialized;
+        }
+    }
+
+    /// Returns the panel setting (`0x00`) written by the init commands, or `None` if they do not
+    /// write it.
+    pub fn panel_setting(&self) -> Option<PanelSetting> {
+        self.registers.panel_setting.or_else(|| {
+            match &self.config.init_commands[panel_setting_index(&self.config.init_commands)?] {
+                Action::SendData(data) => Some(PanelSetting::from_bits(data[0])),
+                _ => None,
+            }
+        })
+    }
+
+    /// Replaces the panel setting (`0x00`) written by the init commands with `setting`, or
+    /// restores the one of the config with `None`. The display is re-initialized on the next
+    /// operation.
+    ///
+    /// The scan directions of `setting` are for an unrotated display, and are still flipped by
+    /// [`set_rotation`](Epd::set_rotation) and [`set_mirroring`](Epd::set_mirroring).
+    /// Returns `Err(EpdError::Unsupported)` if the init commands do not write the panel setting,
+    /// or if `setting` does not drive the colors of the panel.
+    pub fn set_panel_setting(&mut self, setting: Option<PanelSetting>) -> Result<(), EpdError> {
+        let Some(old) = self.panel_setting() else {
+            return Err(EpdError::Unsupported("panel setting"));
+        };
+        if setting.is_some_and(|setting| {
+            setting.black_white != (self.config.colors == PanelColors::BlackWhite)
+        }) {
+            return Err(EpdError::Unsupported("changing the colors of the panel"));
+        }
+        self.registers.panel_setting = setting;
+        let new = self.panel_setting().unwrap_or(old);
+        if (new.scan_up, new.shift_right) != (old.scan_up, old.shift_right) {
+            // The data RAM no longer matches the image on the display in the new direction
+            self.frame = None;
+        }
+        self.reinitialize_later();
+        Ok(())
+    }
+
+    /// Sets the color of the border around the active area of the display, or restores the one
+    /// of the init commands with `None`. The display is re-initialized on the next operation.
+    ///
+    /// The border is changed in every init sequence which selects it in the VCOM and data interval
+    /// setting (`0x50`), including the ones for partial refreshes, which usually let it float.
+    /// Returns `Err(EpdError::Unsupported)` if the init commands do not select the border, or for
+    /// a red border on a black and white display.
+    pub fn set_border(&mut self, border: Option<BorderColor>) -> Result<(), EpdError> {
+        if registers::register_index(&self.config.init_commands, registers::VCOM_DATA_INTERVAL)
+            .is_none()
+        {
+            return Err(EpdError::Unsupported("border"));
+        }
+        if border == Some(BorderColor::Red) && self.config.colors == PanelColors::BlackWhite {
+            return Err(EpdError::Unsupported("red border"));
+        }
+        self.registers.border = border;
+        self.reinitialize_later();
+        Ok(())
+    }
+
+    /// Sets the VCOM DC level in volts (e.g. `-1.5`), which adjusts the contrast, or leaves it to
+    /// the init commands with `None`. The level is rounded to a step of 50 mV. The display is
+    /// re-initialized on the next operation.
+    /// Returns `Err(EpdError::InvalidConfig)` if the controller cannot drive the level, which goes
+    /// from -0.10 V down to -4.05 V on the UC8179 and to -3.00 V on the UC8151.
+    pub fn set_vcom(&mut self, volts: Option<f32>) -> Result<(), EpdError> {
+        self.registers.vcom = match volts {
+            Some(volts) => Some(
+                registers::vcom_bits(self.config.controller, volts).ok_or_else(|| {
+                    EpdError::InvalidConfig(
+                        format!("the controller cannot drive a VCOM of {volts} V").into(),
+                    )
+                })?,
+            ),
+            None => None,
+        };
+        self.reinitialize_later();
+        Ok(())
+    }
+
+    /// Changes the resolution of the display to `width` x `height` pixels, which is sent with the
+    /// resolution setting (`0x61`) of the init commands and sets the size of the images taken by
+    /// [`display`](Epd::display). The display is re-initialized on the next operation.
+    /// Returns `Err(EpdError::InvalidConfig)` if the width is not a multiple of 8 or the
+    /// resolution does not fit in the register of the controller.
+    pub fn set_resolution(&mut self, width: usize, height: usize) -> Result<(), EpdError> {
+        let (max_width, max_height) = registers::max_resolution(self.config.controller);
+        if width > max_width || height > max_height {
+            return Err(EpdError::InvalidConfig(
+                format!(
+                    "{width}x{height} is larger than the controller's {max_width}x{max_height}"
+                )
+                .into(),
+            ));
+        }
+        let config = EpdConfig {
+            width,
+            height,
+            ..self.config.clone()
+        };
+        config.validate()?;
+        self.config = config;
+        self.registers.resolution = Some((width, height));
+        self.frame = None;
+        self.reinitialize_later();
+        Ok(())
+    }
+
+    /// Returns the width of images passed to [`display`](Epd::display) in pixels, which is the
+    /// height of the display when it is rotated by 90 or 270 degrees.
+    pub fn width(&self) -> usize {
+        self.orientation()
+            .dimensions(self.config.width, self.config.height)
+            .0
+    }
+
+    /// Returns the height of images passed to [`display`](Epd::display) in pixels.
+    pub fn height(&self) -> usize {
+        self.orientation()
+            .dimensions(self.config.width, self.config.height)
+            .1
+    }
+
+    /// Returns the transform from the user's orientation to the data RAM of the display,
+    /// including the flips which are left to the controller's scan direction.
+    fn orientation(&self) -> Transform {
+        let (transpose, mut flip_horizontal, mut flip_vertical) = match self.rotation {
+            Rotation::Rotate0 => (false, false, false),
+            Rotation::Rotate90 => (true, true, false),
+            Rotation::Rotate180 => (false, true, true),
+            Rotation::Rotate270 => (true, false, true),
+        };
+        // Mirroring happens before the transposition, which swaps the axes
+        let (mirror_x, mirror_y) = if transpose {
+            (self.mirror_vertical, self.mirror_horizontal)
+        } else {
+            (self.mirror_horizontal, self.mirror_vertical)
+        };
+        flip_horizontal ^= mirror_x;
+        flip_vertical ^= mirror_y;
+        Transform {
+            transpose,
+            flip_horizontal,
+            flip_vertical,
+        }
+    }
+
+    /// Returns the flips done by the controller's scan direction, which is possible if the init
+    /// commands set up the panel setting (`0x00`).
+    fn scan_flips(&self) -> (bool, bool) {
+        let orientation = self.orientation();
+        if panel_setting_index(&self.config.init_commands).is_some() {
+            (orientation.flip_horizontal, orientation.flip_vertical)
+        } else {
+            (false, false)
+        }
+    }
+
+    /// Returns the transform done on the image data before it is sent to the display.
+    fn software_transform(&self) -> Transform {
+        let mut transform = self.orientation();
+        let (horizontal, vertical) = self.scan_flips();
+        transform.flip_horizontal ^= horizontal;
+        transform.flip_vertical ^= vertical;
+        transform
+    }
+
+    /// Returns `commands` with the registers set on the `Epd` written into them, and the scan
+    /// direction bits of the panel setting changed to flip the display as needed.
+    fn adjusted_commands<'a>(&self, commands: &'a [Action]) -> Cow<'a, [Action]> {
+        let (horizontal, vertical) = self.scan_flips();
+        if self.registers == Registers::default() && !horizontal && !vertical {
+            return Cow::Borrowed(commands);
+        }
+        let mut commands = commands.to_vec();
+        self.registers.apply(&self.config, &mut commands);
+        let Some(i) = panel_setting_index(&commands) else {
+            return Cow::Owned(commands);
+        };
+        if let Action::SendData(data) = &mut commands[i] {
+            let mut psr = data.to_vec();
+            // Clearing SHL shifts the source from right to left and clearing UD scans down
+            psr[0] ^= if horizontal { PSR_SHL } else { 0 } | if vertical { PSR_UD } else { 0 };
+            *data = Cow::Owned(psr);
+        }
+        Cow::Owned(commands)
+    }
+
+    /// Sets how full refreshes are adjusted to the temperature, or `None` to leave it to the
+    /// display. Disabled by default.
+    pub fn set_temperature_compensation(&mut self, compensation: Option<TemperatureCompensation>) {
+        // The controller keeps a forced temperature until it is reset
+        if let Some(TemperatureSource::Fixed(_)) = self.temperature_compensation.map(|c| c.source) {
+            self.reinitialize_later();
+        }
+        self.temperature_compensation = compensation;
+        self.cold = false;
+    }
+
+    /// Reads the temperature in °C from the display controller's internal sensor, waking the
+    /// display up if necessary. Returns `Err(EpdError::Unsupported)` if the interface is unable
+    /// to read from the display.
+    pub fn temperature(&mut self) -> Result<f32, EpdError> {
+        block_on(self.temperature_with::<Blocking>())
+    }
+
+    async fn temperature_with<W: Wait>(&mut self) -> Result<f32, EpdError> {
+        self.wake_with::<W>().await?;
+        self.send_command(0x40)?;
+        self.wait_busy_pin::<W>(self.idle_level()).await?;
+        let mut data = [0x00; 2];
+        self.read_response(&mut data)?;
+        let celsius = self.config.controller.temperature(data);
+        log::info!("EPD temperature is {celsius} °C");
+        Ok(celsius)
+    }
+
+    /// Reads the status flags of the display controller. Returns `Err(EpdError::NoResponse)` if
+    /// the display does not answer, which means it is missing or damaged, or
+    /// `Err(EpdError::Unsupported)` if the interface is unable to read from the display.
+    ///
+    /// The display is only woken up if it cannot answer, i.e. if it is in deep sleep or its power
+    /// has been cut.
+    pub fn status(&mut self) -> Result<Status, EpdError> {
+        block_on(self.status_with::<Blocking>())
+    }
+
+    async fn status_with<W: Wait>(&mut self) -> Result<Status, EpdError> {
+        self.make_responsive::<W>().await?;
+        self.send_command(0x71)?;
+        let mut data = [0x00];
+        self.read_response(&mut data)?;
+        // The most significant bit is always clear, so a line which floats high is caught
+        if data[0] & 0x80 != 0 {
+            return Err(EpdError::NoResponse);
+        }
+        Ok(Status::from_bits(data[0]))
+    }
+
+    /// Reads the revision numbers of the display controller, which tell which panel is attached.
+    /// Returns `Err(EpdError::NoResponse)` if the display does not answer, which means it is
+    /// missing or damaged, or `Err(EpdError::Unsupported)` if the interface is unable to read
+    /// from the display.
+    pub fn revision(&mut self) -> Result<Revision, EpdError> {
+        block_on(self.revision_with::<Blocking>())
+    }
+
+    async fn revision_with<W: Wait>(&mut self) -> Result<Revision, EpdError> {
+        self.make_responsive::<W>().await?;
+        self.send_command(0x70)?;
+        let mut data = [0x00; 7];
+        self.read_response(&mut data)?;
+        if data.iter().all(|&b| b == 0x00) || data.iter().all(|&b| b == 0xFF) {
+            return Err(EpdError::NoResponse);
+        }
+        let revision = Revision::from_bytes(data);
+        log::info!("EPD revision is {revision:?}");
+        Ok(revision)
+    }
+
+    /// Wakes the display up if the controller does not answer commands in its current state.
+    async fn make_responsive<W: Wait>(&mut self) -> Result<(), EpdError> {
+        match self.state {
+            EpdState::DeepSleep | EpdState::PowerCut => self.wake_with::<W>().await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the mode to initialize the EPD with for a full refresh, reading the temperature
+    /// first if compensation is enabled.
+    async fn compensated_init_mode<W: Wait>(&mut self) -> Result<InitMode, EpdError> {
+        let celsius = match self.temperature_compensation.map(|c| c.source) {
+            Some(TemperatureSource::Sensor) => Some(self.temperature_with::<W>().await?),
+            Some(TemperatureSource::Fixed(celsius)) => Some(celsius),
+            None => None,
+        };
+        Ok(self.compensate(celsius))
+    }
+
+    fn compensate(&mut self, celsius: Option<f32>) -> InitMode {
+        let cold = match (self.temperature_compensation, celsius) {
+            (Some(compensation), Some(celsius)) => celsius < compensation.fast_min_celsius,
+            _ => false,
+        };
+        if cold && !self.cold && self.refresh_mode == RefreshMode::Fast {
+            log::warn!("EPD is too cold for fast refreshes, falling back to the normal waveform");
+        }
+        self.cold = cold;
+        self.full_init_mode()
+    }
+
+    /// Forces the user-supplied temperature on the controller for normal refreshes. Fast and
+    /// grayscale refreshes already force their own temperature to select their waveform.
+    fn force_temperature(&mut self, mode: InitMode) -> Result<(), EpdError> {
+        let Some(TemperatureSource::Fixed(celsius)) =
+            self.temperature_compensation.map(|c| c.source)
+        else {
+            return Ok(());
+        };
+        if mode != InitMode::Full {
+            return Ok(());
+        }
+        self.send_command(0xE0)?;
+        self.send_data(&[0x02])?;
+        self.send_command(0xE5)?;
+        self.send_data(&[celsius.round() as i8 as u8])
+    }
+
+    fn init_commands(&self, mode: InitMode) -> Result<Cow<'static, [Action]>, EpdError> {
+        match mode {
+            InitMode::Full => Ok(self.config.init_commands.clone()),
+            InitMode::Fast => self
+                .config
+                .fast_init_commands
+                .clone()
+                .ok_or(EpdError::Unsupported("fast refresh")),
+            InitMode::Partial => self
+                .config
+                .partial_init_commands
+                .clone()
+                .ok_or(EpdError::Unsupported("partial refresh")),
+            InitMode::Gray4 => self
+                .config
+                .gray4_init_commands
+                .clone()
+                .ok_or(EpdError::Unsupported("4-level grayscale")),
+        }
+    }
+
+    async fn load<W: Wait>(&mut self, mode: InitMode) -> Result<(), EpdError> {
+        let commands = self.init_commands(mode)?;
+        self.state = EpdState::Uninitialized;
+        if !matches!(commands.first(), Some(Action::HardwareReset { .. })) {
+            self.reset::<W>(RESET_TIMINGS).await?;
+        }
+        let commands = self.adjusted_commands(&commands).into_owned();
+        self.run_actions::<W>(&commands).await?;
+        self.state = EpdState::Ready;
+        self.init_mode = mode;
+        self.data_inverted = registers::commands_inverted(&self.config, &commands);
+        Ok(())
+    }
+
+    /// Makes sure the EPD is ready and initialized with the commands for `mode`.
+    async fn prepare<W: Wait>(&mut self, mode: InitMode) -> Result<(), EpdError> {
+        if self.init_mode == mode {
+            self.wake_with::<W>().await
+        } else {
+            self.load::<W>(mode).await
+        }
+    }
+
+    async fn run_actions<W: Wait>(&mut self, actions: &[Action]) -> Result<(), EpdError> {
+        for action in actions {
+            match action {
                 Action::SendCommand(command) => {
-                    self.send_command(command);
+                    self.send_command(*command)?;
                 }
                 Action::SendData(data) => {
-                    self.send_data(data);
+                    self.send_data(data)?;
                 }
                 Action::ReadBusy => {
-                    self.read_busy();
+                    self.read_busy_with::<W>().await?;
                 }
                 Action::Delay(ms) => {
-                    sleep(Duration::from_millis(ms));
+                    W::sleep(Duration::from_millis(*ms)).await;
                 }
+                Action::HardwareReset {
+                    before_ms,
+                    low_ms,
+                    after_ms,
+                } => {
+                    self.reset::<W>([*before_ms, *low_ms, *after_ms]).await?;
+                }
+                Action::SetPower(high) => {
+                    self.set_pwr(*high)?;
+                }
+                Action::WaitBusyPin(level) => {
+                    self.wait_busy_pin::<W>(*level).await?;
+                }
+                Action::Repeat { count, actions } => {
+                    for _ in 0..*count {
+                        Box::pin(self.run_actions::<W>(actions)).await?;
+                    }
+                }
+                Action::SendResolution => {
+                    self.send_data(&self.resolution())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Brings the EPD back to the [`Ready`](EpdState::Ready) state, powering it back on after
+    /// [`power_off`](Epd::power_off) or resetting and re-initializing it after [`sleep`](Epd::sleep).
+    pub fn wake(&mut self) -> Result<(), EpdError> {
+        block_on(self.wake_with::<Blocking>())
+    }
+
+    async fn wake_with<W: Wait>(&mut self) -> Result<(), EpdError> {
+        match self.state {
+            EpdState::Ready => Ok(()),
+            EpdState::PoweredOff => {
+                log::info!("Powering on EPD");
+                self.run_actions::<W>(&self.config.power_on_commands.clone())
+                    .await?;
+                self.state = EpdState::Ready;
+                Ok(())
+            }
+            EpdState::Uninitialized | EpdState::DeepSleep => self.load::<W>(self.init_mode).await,
+            EpdState::PowerCut => {
+                log::info!("Restoring power of EPD");
+                self.set_pwr(true)?;
+                W::sleep(Duration::from_millis(PWR_ON_DELAY)).await;
+                self.load::<W>(self.init_mode).await
             }
         }
     }
 
     /// Returns the number of bytes that the EPD takes in for displaying an image.
     pub fn image_buffer_size(&self) -> usize {
-        self.config.height * self.config.width / 8
+        self.width().div_ceil(8) * self.height()
     }
 
-    fn reset(&mut self) {
-        self.rpi.gpio.rst.set_high();
-        sleep(Duration::from_millis(20));
-        self.rpi.gpio.rst.set_low();
-        sleep(Duration::from_millis(2));
-        self.rpi.gpio.rst.set_high();
-        sleep(Duration::from_millis(20));
+    /// Returns the size of the data RAMs of the EPD in bytes.
+    fn ram_size(&self) -> usize {
+        self.config.width / 8 * self.config.height
+    }
+
+    /// Holds the RST pin high, low and high again for the given number of milliseconds.
+    async fn reset<W: Wait>(&mut self, timings: [u64; 3]) -> Result<(), EpdError> {
+        self.record(|| Event::Reset(timings));
+        for (high, ms) in [true, false, true].into_iter().zip(timings) {
+            self.interface.set_rst(high)?;
+            W::sleep(Duration::from_millis(ms)).await;
+        }
+        Ok(())
+    }
+
+    fn resolution(&self) -> Vec<u8> {
+        self.config
+            .controller
+            .resolution(self.config.width, self.config.height)
+    }
+
+    fn send_command(&mut self, command: u8) -> Result<(), EpdError> {
+        self.last_activity = Instant::now();
+        self.record(|| Event::Command(command));
+        self.interface.set_dc(false)?;
+        self.interface.set_cs(false)?;
+        // CS goes high again even if the write fails, so a shared bus is released
+        let result = self.interface.spi_write(&[command]);
+        self.interface.set_cs(true)?;
+        result
     }
 
-    fn send_command(&mut self, command: u8) {
-        self.rpi.gpio.dc.set_low();
-        self.rpi.gpio.cs.set_low();
-        self.rpi.spi.write(&[command]).unwrap();
-        self.rpi.gpio.cs.set_high();
+    /// Reads the response to the last command into `buffer`.
+    fn read_response(&mut self, buffer: &mut [u8]) -> Result<(), EpdError> {
+        self.interface.set_dc(true)?;
+        self.interface.set_cs(false)?;
+        let result = self.interface.spi_read(buffer);
+        self.interface.set_cs(true)?;
+        if result.is_ok() {
+            self.record(|| Event::Read(buffer.to_vec()));
+        }
+        result
     }
 
-    fn send_data(&mut self, data: &[u8]) {
+    fn send_data(&mut self, data: &[u8]) -> Result<(), EpdError> {
+        self.record(|| Event::Data(data.to_vec()));
         let chunks = data.chunks(DATA_BUFFER_SIZE);
         for chunk in chunks {
-            self.rpi.gpio.dc.set_high();
-            self.rpi.gpio.cs.set_low();
-            self.rpi.spi.write(chunk).unwrap();
-            self.rpi.gpio.cs.set_high();
+            self.interface.set_dc(true)?;
+            self.interface.set_cs(false)?;
+            let result = self.interface.spi_write(chunk);
+            self.interface.set_cs(true)?;
+            result?;
         }
+        Ok(())
+    }
+
+    /// Waits on the busy pin and returns once the EPD stops being busy.
+    /// Returns `Err(EpdError::BusyTimeout)` if the EPD is still busy once the busy timeout elapses.
+    pub fn read_busy(&mut self) -> Result<(), EpdError> {
+        block_on(self.read_busy_with::<Blocking>())
     }
 
-    /// Constantly read from the busy pin and returns once the EPD stops being busy.
-    pub fn read_busy(&mut self) {
+    async fn read_busy_with<W: Wait>(&mut self) -> Result<(), EpdError> {
         log::info!("Waiting until EPD is no longer busy");
-        self.send_command(0x71);
-        let mut busy = self.rpi.gpio.busy.read();
-        while busy == Level::Low {
-            sleep(Duration::from_millis(100));
-            busy = self.rpi.gpio.busy.read();
+        if let Some(command) = self.config.busy_command {
+            self.send_command(command)?;
         }
+        self.wait_busy_pin::<W>(self.idle_level()).await?;
         log::info!("EPD is no longer busy");
+        Ok(())
     }
 
-    /// Clears the screen by setting it all pixels to wwhite
-    pub fn clear(&mut self) {
-        // TODO support Black&White&Red displays
-        log::info!("Clearing EPD");
-        self.send_command(0x10);
-        let blank = vec![0x00; self.image_buffer_size()];
-        self.send_data(&blank);
-        self.send_command(0x13);
-        self.send_data(&blank);
-        self.send_command(0x12);
-        sleep(Duration::from_millis(100));
-        self.read_busy();
+    /// Returns the level of the busy pin while the EPD is not busy.
+    fn idle_level(&self) -> bool {
+        self.config.busy_polarity == BusyPolarity::ActiveLow
     }
 
-    /// Takes in image data (represented in an array of `u8`) and displays it on the EPD.
-    /// Returns `Err(ImgSizeMismatchError)` if the size of image data does not match the EPD's config.
-    ///
-    /// # Image data format for Black & White displays
-    ///
-    /// Each bit in the `u8` array represents a pixel. If the bit is set, the pixel will be black.
-    /// Likewise if the bit is unset, the pixel will be white. The EPD will draw from left to right
-    /// based on the input array starting from the top-left, and will wrap back to the left side of
-    /// the next row when it reaches the right side of the current row
-    pub fn display(&mut self, data: &[u8]) -> Result<(), ImgSizeMismatchError> {
-        if data.len() != self.image_buffer_size() {
-            return Err(ImgSizeMismatchError);
+    /// Waits until the busy pin is at `level`, giving up once the busy timeout elapses.
+    async fn wait_busy_pin<W: Wait>(&mut self, level: bool) -> Result<(), EpdError> {
+        let time = self.recording_time();
+        let start = Instant::now();
+        let result = self.poll_busy_pin::<W>(start, level).await;
+        self.record_at(time, || Event::Busy {
+            level,
+            waited: start.elapsed(),
+        });
+        result
+    }
+
+    async fn poll_busy_pin<W: Wait>(
+        &mut self,
+        start: Instant,
+        level: bool,
+    ) -> Result<(), EpdError> {
+        while let Some(wait) = self.busy_wait(start, level)? {
+            W::busy_edge(&mut self.interface, wait).await?;
         }
-        log::info!("Displaying image on EPD");
-        self.send_command(0x13);
-        self.send_data(data);
-        self.send_command(0x12);
-        sleep(Duration::from_millis(100));
-        self.read_busy();
         Ok(())
     }
 
-    /// Puts the display to a low power consumption state.
-    pub fn sleep(&mut self) {
-        log::info!("Sleeping EPD");
-        self.send_command(0x02);
-        self.read_busy();
-        self.send_command(0x07);
-        self.send_data(&[0xA5]);
-        sleep(Duration::from_millis(1500));
+    /// Returns how long to wait before checking the busy pin again, or `None` if it is at `level`.
+    /// Returns `Err(EpdError::BusyTimeout)` once the busy timeout since `start` elapses.
+    fn busy_wait(&mut self, start: Instant, level: bool) -> Result<Option<Duration>, EpdError> {
+        if self.interface.busy_high()? == level {
+            return Ok(None);
+        }
+        let mut wait = self.busy_poll_interval;
+        if let Some(timeout) = self.busy_timeout {
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                log::error!("EPD is still busy after {timeout:?}");
+                return Err(EpdError::BusyTimeout);
+            }
+            wait = wait.min(timeout - elapsed);
+        }
+        Ok(Some(wait))
+    }
+
+    /// Starts a refresh of the display with the contents of the data RAMs and waits for it to finish.
+    async fn refresh<W: Wait>(&mut self) -> Result<(), EpdError> {
+        self.run_actions::<W>(&self.config.refresh_commands.clone())
+            .await?;
+        self.record_refresh();
+        Ok(())
+    }
+
+    /// Clears the screen by setting it all pixels to wwhite
+    pub fn clear(&mut self) -> Result<(), EpdError> {
+        block_on(self.clear_with::<Blocking>())
+    }
+
+    async fn clear_with<W: Wait>(&mut self) -> Result<(), EpdError> {
+        let mode = self.compensated_init_mode::<W>().await?;
+        let mode = self.clean_mode(mode, None);
+        self.prepare::<W>(mode).await?;
+        self.force_temperature(mode)?;
+        self.frame = None;
+        self.send_clear()?;
+        self.refresh::<W>().await?;
+        self.frame = Some(self.blank_frame());
+        Ok(())
+    }
+
+    /// Returns the cache of a white display.
+    fn blank_frame(&self) -> FrameCache {
+        let blank = vec![0x00; self.ram_size()];
+        FrameCache::new(&self.config, self.scan_flips(), &Frame::BlackWhite(&blank))
+    }
+
+    fn send_clear(&mut self) -> Result<(), EpdError> {
+        log::info!("Clearing EPD");
+        let blank = vec![0x00; self.ram_size()];
+        match self.config.colors {
+            PanelColors::BlackWhite => {
+                let blank = self.ram_data(&blank);
+                self.send_command(0x10)?;
+                self.send_data(&blank)?;
+                self.send_command(0x13)?;
+                self.send_data(&blank)
+            }
+            PanelColors::BlackWhiteRed => self.send_black_red(&blank, &blank),
+        }
+    }
+
+    /// Takes in image data (represented in an array of `u8` or a [`Frame`]) and displays it on the EPD.
+    /// Returns `Err(EpdError::SizeMismatch)` if the size of image data does not match the EPD's config,
+    /// or `Err(EpdError::Unsupported)` if the display model does not support the frame's color mode.
+    ///
+    /// # Image data format for Black & White displays
+    ///
+    /// Each bit in the `u8` array represents a pixel. If the bit is set, the pixel will be black.
+    /// Likewise if the bit is unset, the pixel will be white. The EPD will draw from left to right
+    /// based on the input array starting from the top-left, and will wrap back to the left side of
+    /// the next row when it reaches the right side of the current row
+    ///
+    /// # Image data format for 4-level grayscale
+    ///
+    /// Grayscale images are passed as the two bit-planes of a [`Frame::Gray4`], which can be
+    /// produced by [`converter::image_to_epd_planes`]. The display is initialized for grayscale
+    /// before the image is displayed, and switched back to the current [`RefreshMode`] by the next
+    /// black and white image. Grayscale images are always displayed with a full refresh.
+    ///
+    /// # Image data format for Black & White & Red displays
+    ///
+    /// Black and red are passed as two separate planes of a [`Frame::BlackWhiteRed`], which can be
+    /// produced by [`converter::image_to_epd_planes`]. If the bit of a pixel is set in the red
+    /// plane, the pixel will be red regardless of the black plane. Black and white images can be
+    /// displayed on these displays as well.
+    ///
+    /// # Skipped and partial refreshes
+    ///
+    /// Nothing is sent to the display if the image is already shown, as far as the `Epd` knows
+    /// from the images it displayed itself or from [`set_frame_cache`](Epd::set_frame_cache).
+    /// If [`set_auto_partial`](Epd::set_auto_partial) is enabled, a black and white image which
+    /// only differs from the shown one in a small area is displayed with a partial refresh of that
+    /// area.
+    pub fn display<'a>(&mut self, frame: impl Into<Frame<'a>>) -> Result<(), EpdError> {
+        block_on(self.display_with::<Blocking>(frame.into()))
+    }
+
+    async fn display_with<W: Wait>(&mut self, frame: Frame<'_>) -> Result<(), EpdError> {
+        let mut mode = self.frame_init_mode(&frame)?;
+        let planes = self.oriented_planes(&frame);
+        let frame = frame.with_planes(&planes);
+        let (cache, changed_area) = match self.frame_update(&frame) {
+            FrameUpdate::Unchanged => {
+                log::info!("Image is already shown on EPD");
+                return Ok(());
+            }
+            FrameUpdate::Region(update) => {
+                let changed_area = update.width * update.height;
+                if !self.clean_due(Some(changed_area)) {
+                    return self.update_region::<W>(update).await;
+                }
+                log::info!("Using a full refresh instead of a partial refresh to clear ghosting");
+                (update.frame, Some(changed_area))
+            }
+            FrameUpdate::Full {
+                frame,
+                changed_area,
+            } => (frame, changed_area),
+        };
+        if mode != InitMode::Gray4 {
+            mode = self.compensated_init_mode::<W>().await?;
+            mode = self.clean_mode(mode, changed_area);
+        }
+        self.full_refresh::<W>(&frame, cache, mode).await
+    }
+
+    /// Shows `frame`, which is in the layout of the data RAMs, with a full refresh in `mode` and
+    /// remembers it as `cache`.
+    async fn full_refresh<W: Wait>(
+        &mut self,
+        frame: &Frame<'_>,
+        cache: FrameCache,
+        mode: InitMode,
+    ) -> Result<(), EpdError> {
+        self.prepare::<W>(mode).await?;
+        self.force_temperature(mode)?;
+        self.frame = None;
+        self.send_frame(frame)?;
+        self.refresh::<W>().await?;
+        self.frame = Some(cache);
+        Ok(())
+    }
+
+    /// Works out how to get from the image shown on the display to `frame`, which is in the
+    /// layout of the data RAMs.
+    fn frame_update(&self, frame: &Frame) -> FrameUpdate {
+        let cache = FrameCache::new(&self.config, self.scan_flips(), frame);
+        let Some(old) = &self.frame else {
+            return FrameUpdate::Full {
+                frame: cache,
+                changed_area: None,
+            };
+        };
+        if old.shows(&cache) {
+            return FrameUpdate::Unchanged;
+        }
+        let region = old.changed_region(&cache);
+        match (self.auto_partial, region) {
+            (Some(max_area), Some(region @ (_, _, width, height)))
+                if (width * height) as f32 <= max_area * cache.area() as f32 =>
+            {
+                FrameUpdate::Region(self.ram_region(old.black(), cache, region))
+            }
+            _ => FrameUpdate::Full {
+                frame: cache,
+                changed_area: region.map(|(_, _, width, height)| width * height),
+            },
+        }
+    }
+
+    /// Checks that `frame` can be displayed and returns the mode to initialize the EPD with for it.
+    fn frame_init_mode(&self, frame: &Frame) -> Result<InitMode, EpdError> {
+        for plane in frame.planes() {
+            if plane.len() != self.image_buffer_size() {
+                return Err(EpdError::SizeMismatch {
+                    expected: self.image_buffer_size(),
+                    actual: plane.len(),
+                });
+            }
+        }
+        match (frame, self.config.colors) {
+            (Frame::BlackWhiteRed { .. }, PanelColors::BlackWhite) => {
+                Err(EpdError::Unsupported("red on a black and white display"))
+            }
+            (Frame::Gray4 { .. }, _) => Ok(InitMode::Gray4),
+            _ => Ok(self.full_init_mode()),
+        }
+    }
+
+    /// Transforms the planes of `frame` to the layout of the data RAMs.
+    fn oriented_planes<'a>(&self, frame: &Frame<'a>) -> Vec<Cow<'a, [u8]>> {
+        let transform = self.software_transform();
+        frame
+            .planes()
+            .into_iter()
+            .map(|plane| transform.apply(plane, self.width(), self.height()))
+            .collect()
+    }
+
+    /// Writes `frame` to the data RAMs of the EPD.
+    fn send_frame(&mut self, frame: &Frame) -> Result<(), EpdError> {
+        match (*frame, self.config.colors) {
+            (Frame::BlackWhite(data), PanelColors::BlackWhite) => {
+                log::info!("Displaying image on EPD");
+                let data = self.ram_data(data);
+                self.send_command(0x13)?;
+                self.send_data(&data)
+            }
+            (Frame::BlackWhite(black), PanelColors::BlackWhiteRed) => {
+                log::info!("Displaying image on EPD");
+                let red = vec![0x00; self.ram_size()];
+                self.send_black_red(black, &red)
+            }
+            (Frame::BlackWhiteRed { black, red }, _) => {
+                log::info!("Displaying black, white and red image on EPD");
+                self.send_black_red(black, red)
+            }
+            (Frame::Gray4 { lsb, msb }, _) => {
+                log::info!("Displaying grayscale image on EPD");
+                self.send_command(0x10)?;
+                self.send_data(lsb)?;
+                self.send_command(0x13)?;
+                self.send_data(msb)
+            }
+        }
+    }
+
+    /// Sends the black and red planes to a tri-color controller, which takes the black/white data
+    /// in the first RAM and set bits as red in the red RAM.
+    fn send_black_red(&mut self, black: &[u8], red: &[u8]) -> Result<(), EpdError> {
+        let black = self.ram_data(black);
+        self.send_command(0x10)?;
+        self.send_data(&black)?;
+        self.send_command(0x13)?;
+        self.send_data(red)
+    }
+
+    /// Updates a rectangular region of the EPD using a partial refresh, which does not flash the
+    /// rest of the display. Returns `Err(EpdError::Unsupported)` if the display model does not
+    /// support partial refreshes.
+    ///
+    /// `data` holds `height` rows of `width.div_ceil(8)` bytes in the same format as
+    /// [`display`](Epd::display), each row starting at the most significant bit of its first byte.
+    /// `x` does not need to be a multiple of 8: the controller can only update whole bytes, so the
+    /// refreshed area is widened to the byte boundaries and filled with the image previously shown
+    /// through this `Epd` (or white if nothing has been displayed yet).
+    ///
+    /// If the [`GhostingPolicy`] calls for it, the whole display is updated with a normal full
+    /// refresh instead, showing the region on top of the black and white image previously shown.
+    pub fn display_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<(), EpdError> {
+        block_on(self.display_region_with::<Blocking>(x, y, width, height, data))
+    }
+
+    async fn display_region_with<W: Wait>(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<(), EpdError> {
+        let update = self.region_update(x, y, width, height, data)?;
+        if !self.clean_due(Some(update.width * update.height)) {
+            return self.update_region::<W>(update).await;
+        }
+        log::info!("Using a full refresh instead of a partial refresh to clear ghosting");
+        let (frame, cache) = self.cleaned_region(update);
+        self.full_refresh::<W>(&Frame::BlackWhite(&frame), cache, InitMode::Full)
+            .await
+    }
+
+    /// Returns the black and white image to show with a full refresh instead of the partial
+    /// refresh of `update`, and its cache.
+    fn cleaned_region(&self, update: RegionUpdate) -> (Vec<u8>, FrameCache) {
+        let frame = update.frame.black().to_vec();
+        let cache = FrameCache::new(&self.config, self.scan_flips(), &Frame::BlackWhite(&frame));
+        (frame, cache)
+    }
+
+    /// Displays a region worked out by [`region_update`](Epd::region_update) with a partial
+    /// refresh.
+    async fn update_region<W: Wait>(&mut self, update: RegionUpdate) -> Result<(), EpdError> {
+        self.prepare::<W>(InitMode::Partial).await?;
+        self.frame = None;
+        self.send_region(&update)?;
+        self.refresh::<W>().await?;
+        self.frame = Some(update.frame);
+        self.send_command(0x92)
+    }
+
+    /// Checks the region and works out what to send to the EPD to update it with `data`.
+    fn region_update(
+        &self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<RegionUpdate, EpdError> {
+        if width == 0 || height == 0 || x + width > self.width() || y + height > self.height() {
+            return Err(EpdError::RegionOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            });
+        }
+        let row_bytes = width.div_ceil(8);
+        if data.len() != row_bytes * height {
+            return Err(EpdError::SizeMismatch {
+                expected: row_bytes * height,
+                actual: data.len(),
+            });
+        }
+
+        // Work in the layout of the data RAMs from here on
+        let transform = self.software_transform();
+        let data = transform.apply(data, width, height);
+        let (x, y) = transform.position((x, y, width, height), (self.width(), self.height()));
+        let (width, height) = transform.dimensions(width, height);
+        let row_bytes = width.div_ceil(8);
+
+        let epd_row_bytes = self.config.width / 8;
+        let blank = vec![0x00; self.ram_size()];
+        let old_frame = self.frame.as_ref().map_or(&blank[..], FrameCache::black);
+        let mut new_frame = old_frame.to_vec();
+        for row in 0..height {
+            for col in 0..width {
+                let bit = data[row * row_bytes + col / 8] & (0x80 >> (col % 8)) != 0;
+                let i = (y + row) * epd_row_bytes + (x + col) / 8;
+                let mask = 0x80 >> ((x + col) % 8);
+                if bit {
+                    new_frame[i] |= mask;
+                } else {
+                    new_frame[i] &= !mask;
+                }
+            }
+        }
+
+        // Parts of the display outside the region are assumed to be white if nothing is known
+        let frame = match &self.frame {
+            Some(cache) => cache.with_black(new_frame),
+            None => FrameCache::approximate(&self.config, self.scan_flips(), new_frame),
+        };
+        Ok(self.ram_region(old_frame, frame, (x, y, width, height)))
+    }
+
+    /// Works out the partial refresh of a region of the data RAMs from `old`, the black and white
+    /// image shown on the display, to `frame`.
+    fn ram_region(
+        &self,
+        old: &[u8],
+        frame: FrameCache,
+        (x, y, width, height): (usize, usize, usize, usize),
+    ) -> RegionUpdate {
+        // Widen the window to whole bytes
+        let epd_row_bytes = self.config.width / 8;
+        let first_byte = x / 8;
+        let last_byte = (x + width - 1) / 8;
+        let window = |frame: &[u8]| -> Vec<u8> {
+            (y..y + height)
+                .flat_map(|row| {
+                    frame[row * epd_row_bytes + first_byte..=row * epd_row_bytes + last_byte]
+                        .iter()
+                        .copied()
+                })
+                .collect()
+        };
+        let x_start = first_byte * 8;
+        let x_end = last_byte * 8 + 7;
+        let y_end = y + height - 1;
+        RegionUpdate {
+            x,
+            y,
+            width,
+            height,
+            window: [
+                (x_start >> 8) as u8,
+                x_start as u8,
+                (x_end >> 8) as u8,
+                x_end as u8,
+                (y >> 8) as u8,
+                y as u8,
+                (y_end >> 8) as u8,
+                y_end as u8,
+                0x01,
+            ],
+            old: window(old),
+            new: window(frame.black()),
+            frame,
+        }
+    }
+
+    /// Enters partial mode and writes the window of `update` to the data RAMs of the EPD, in the
+    /// polarity selected by the partial init commands.
+    fn send_region(&mut self, update: &RegionUpdate) -> Result<(), EpdError> {
+        let RegionUpdate {
+            x,
+            y,
+            width,
+            height,
+            ..
+        } = *update;
+        log::info!("Displaying {width}x{height} region at ({x}, {y}) on EPD");
+        self.send_command(0x91)?;
+        self.send_command(0x90)?;
+        self.send_data(&update.window)?;
+        let old = self.ram_data(&update.old).into_owned();
+        let new = self.ram_data(&update.new).into_owned();
+        self.send_command(0x10)?;
+        self.send_data(&old)?;
+        self.send_command(0x13)?;
+        self.send_data(&new)
+    }
+
+    /// Converts black and white data, where set bits are black, to the polarity of the
+    /// black/white data RAM selected by the init commands the EPD was last initialized with.
+    fn ram_data<'a>(&self, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.data_inverted {
+            Cow::Owned(data.iter().map(|b| !b).collect())
+        } else {
+            Cow::Borrowed(data)
+        }
+    }
+
+    /// Removes ghosting and burn-in by driving the whole display black and white a few times with
+    /// normal full refreshes. The image shown before is displayed again afterwards if it is known
+    /// (see [`frame_cache`](Epd::frame_cache)), otherwise the display is left white.
+    pub fn deep_clean(&mut self) -> Result<(), EpdError> {
+        block_on(self.deep_clean_with::<Blocking>())
+    }
+
+    async fn deep_clean_with<W: Wait>(&mut self) -> Result<(), EpdError> {
+        log::info!("Deep cleaning EPD");
+        let restore = self.frame.take().filter(FrameCache::is_exact);
+        for (frame, cache) in self.deep_clean_frames() {
+            self.full_refresh::<W>(&Frame::BlackWhite(&frame), cache, InitMode::Full)
+                .await?;
+        }
+        if let Some(cache) = restore {
+            let frame = cache.frame().unwrap();
+            let mode = restore_mode(&frame);
+            self.full_refresh::<W>(&frame, cache.clone(), mode).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the black and white images shown one after another by
+    /// [`deep_clean`](Epd::deep_clean), with their caches.
+    fn deep_clean_frames(&self) -> Vec<(Vec<u8>, FrameCache)> {
+        [0xFF, 0x00]
+            .repeat(DEEP_CLEAN_CYCLES)
+            .into_iter()
+            .map(|fill| {
+                let frame = vec![fill; self.ram_size()];
+                let cache =
+                    FrameCache::new(&self.config, self.scan_flips(), &Frame::BlackWhite(&frame));
+                (frame, cache)
+            })
+            .collect()
+    }
+
+    /// Powers off the display while keeping its settings, so it can be powered on again quickly.
+    pub fn power_off(&mut self) -> Result<(), EpdError> {
+        block_on(self.power_off_with::<Blocking>())
+    }
+
+    async fn power_off_with<W: Wait>(&mut self) -> Result<(), EpdError> {
+        if self.state != EpdState::Ready {
+            return Ok(());
+        }
+        log::info!("Powering off EPD");
+        self.run_actions::<W>(&self.config.power_off_commands.clone())
+            .await?;
+        self.state = EpdState::PoweredOff;
+        Ok(())
+    }
+
+    /// Puts the display to a low power consumption state. The display needs to be reset and
+    /// re-initialized before it can be used again, which is done by the next call to
+    /// [`wake`](Epd::wake), [`display`](Epd::display) or [`clear`](Epd::clear).
+    pub fn sleep(&mut self) -> Result<(), EpdError> {
+        block_on(self.sleep_with::<Blocking>())
+    }
+
+    async fn sleep_with<W: Wait>(&mut self) -> Result<(), EpdError> {
+        if matches!(self.state, EpdState::DeepSleep | EpdState::PowerCut) {
+            return Ok(());
+        }
+        log::info!("Sleeping EPD");
+        self.run_actions::<W>(&self.config.sleep_commands.clone())
+            .await?;
+        self.state = EpdState::DeepSleep;
+        Ok(())
     }
 }
 
-impl Drop for Epd {
-    fn drop(&mut self) {
-        self.sleep();
+impl<I: EpdInterface + Send + 'static> Epd<I> {
+    /// Spawns a thread which applies the idle policy of `epd` with
+    /// [`poll_idle`](Epd::poll_idle) while the `Epd` is in use. The thread stops once all other
+    /// references to `epd` have been dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd, IdlePolicy};
+    ///
+    /// let mut epd = Epd::new(EPD_CONFIG).unwrap();
+    /// epd.set_idle_policy(Some(IdlePolicy::deep_sleep(Duration::from_secs(60))));
+    /// let epd = Arc::new(Mutex::new(epd));
+    /// Epd::watch_idle(&epd);
+    ///
+    /// epd.lock().unwrap().clear().unwrap();
+    /// ```
+    pub fn watch_idle(epd: &Arc<Mutex<Self>>) -> JoinHandle<()> {
+        let epd = Arc::downgrade(epd);
+        thread::spawn(move || loop {
+            let wait = {
+                let Some(epd) = epd.upgrade() else {
+                    return;
+                };
+                let mut epd = epd.lock().unwrap_or_else(PoisonError::into_inner);
+                match epd.poll_idle() {
+                    Ok(_) => epd
+                        .idle_deadline()
+                        .map_or(IDLE_WATCHDOG_INTERVAL, |deadline| {
+                            deadline
+                                .saturating_duration_since(Instant::now())
+                                .min(IDLE_WATCHDOG_INTERVAL)
+                        }),
+                    // Try again later instead of spinning on the deadline
+                    Err(e) => {
+                        log::error!("Failed to power down idle EPD: {e}");
+                        IDLE_WATCHDOG_INTERVAL
+                    }
+                }
+            };
+            sleep(wait);
+        })
+    }
+}
+
+/// Window and RAM contents for a partial refresh of a region of the display.
+struct RegionUpdate {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    /// Data of the partial window command (`0x90`).
+    window: [u8; 9],
+    /// Black and white image inside the window before and after the update, with set bits black.
+    old: Vec<u8>,
+    new: Vec<u8>,
+    /// The whole frame once the region has been updated.
+    frame: FrameCache,
+}
+
+/// What [`Epd::display`] has to do to show a frame.
+enum FrameUpdate {
+    /// The frame is already shown.
+    Unchanged,
+    /// Only a region of the frame changed.
+    Region(RegionUpdate),
+    /// The frame has to be shown with a full refresh, after which it is cached.
+    Full {
+        frame: FrameCache,
+        /// Number of pixels covered by the bounding box of the changes, if known.
+        changed_area: Option<usize>,
+    },
+}
+
+/// Returns the mode to show a frame from a [`FrameCache`] with a normal full refresh in.
+fn restore_mode(frame: &Frame) -> InitMode {
+    match frame {
+        Frame::Gray4 { .. } => InitMode::Gray4,
+        _ => InitMode::Full,
     }
 }
 
-impl Default for Epd {
-    fn default() -> Self {
-        use epd_configs::epd7in5_v2::EPD_CONFIG;
-        Self::new(EPD_CONFIG)
+/// Returns the index of the data of the panel setting command (`0x00`) in `commands`.
+fn panel_setting_index(commands: &[Action]) -> Option<usize> {
+    registers::register_index(commands, PANEL_SETTING)
+}
+
+impl<I: EpdInterface> Drop for Epd<I> {
+    fn drop(&mut self) {
+        // A controller which failed to initialize would only keep the caller waiting on BUSY
+        if self.state == EpdState::Uninitialized {
+            return;
+        }
+        if let Err(e) = self.sleep() {
+            log::error!("Failed to put EPD to sleep: {e}");
+        }
     }
 }
 
@@ -202,10 +1828,831 @@ impl Default for Epd {
 mod tests {
     use super::*;
 
+    use epd_configs::epd7in5_v2::EPD_CONFIG;
+    use simulator::EpdSimulator;
+
+    fn simulated_epd() -> Epd<EpdSimulator> {
+        Epd::with_interface(EPD_CONFIG, EpdSimulator::new(&EPD_CONFIG)).unwrap()
+    }
+
+    /// A file in the temporary directory, named after the test and the process so that
+    /// concurrent test runs do not collide, which is removed once dropped.
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("waveshare_rpi_{}_{name}", std::process::id());
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
     #[test]
     fn clear_test() {
-        use epd_configs::epd7in5_v2::EPD_CONFIG;
-        let mut epd = Epd::new(EPD_CONFIG);
-        epd.clear();
+        let mut epd = simulated_epd();
+        epd.clear().unwrap();
+        let sim = epd.interface();
+        assert_eq!(sim.refresh_count(), 1);
+        assert!(sim.visible_image().pixels().all(|p| p.0 == [255]));
+    }
+
+    #[test]
+    fn display_test() {
+        let mut epd = simulated_epd();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        // Top-left pixel and the last pixel of the first row
+        data[0] = 0x80;
+        data[EPD_CONFIG.width / 8 - 1] = 0x01;
+        epd.display(&data).unwrap();
+
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(0, 0).0, [0]);
+        assert_eq!(img.get_pixel(1, 0).0, [255]);
+        assert_eq!(img.get_pixel(EPD_CONFIG.width as u32 - 1, 0).0, [0]);
+        assert_eq!(img.get_pixel(0, 1).0, [255]);
+
+        let file = TempFile::new("display_test.png");
+        epd.interface().save_png(&file.0).unwrap();
+        assert_eq!(image::open(&file.0).unwrap().to_luma8(), img);
+    }
+
+    #[test]
+    fn display_size_mismatch_test() {
+        let mut epd = simulated_epd();
+        assert!(matches!(
+            epd.display(&[0x00; 8]),
+            Err(EpdError::SizeMismatch { actual: 8, .. })
+        ));
+        assert_eq!(epd.interface().refresh_count(), 0);
+    }
+
+    #[test]
+    fn display_region_test() {
+        let mut epd = simulated_epd();
+        epd.clear().unwrap();
+        // A 10x2 black bar which starts in the middle of a byte
+        epd.display_region(3, 5, 10, 2, &[0xFF, 0xC0, 0xFF, 0xC0])
+            .unwrap();
+
+        let img = epd.interface().visible_image();
+        for x in 0..16 {
+            let expected = if (3..13).contains(&x) { [0] } else { [255] };
+            assert_eq!(img.get_pixel(x, 5).0, expected);
+            assert_eq!(img.get_pixel(x, 6).0, expected);
+            assert_eq!(img.get_pixel(x, 7).0, [255]);
+        }
+        assert_eq!(epd.interface().refresh_count(), 2);
+
+        assert!(matches!(
+            epd.display_region(795, 0, 10, 1, &[0x00, 0x00]),
+            Err(EpdError::RegionOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn fast_refresh_test() {
+        let mut epd = simulated_epd();
+        epd.set_refresh_mode(RefreshMode::Fast).unwrap();
+        let data = vec![0xFF; epd.image_buffer_size()];
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().register(0xE5), Some(&[0x5A][..]));
+        assert!(epd.interface().visible_image().pixels().all(|p| p.0 == [0]));
+
+        epd.set_refresh_mode(RefreshMode::Normal).unwrap();
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0xE5), None);
+    }
+
+    #[test]
+    fn gray4_test() {
+        let mut epd = simulated_epd();
+        let size = epd.image_buffer_size();
+        // White, light gray, dark gray and black in the first four pixels
+        let mut lsb = vec![0x00; size];
+        let mut msb = vec![0x00; size];
+        lsb[0] = 0b0101_0000;
+        msb[0] = 0b0011_0000;
+        epd.display(Frame::Gray4 {
+            lsb: &lsb,
+            msb: &msb,
+        })
+        .unwrap();
+
+        let img = epd.interface().visible_image();
+        let row: Vec<u8> = (0..5).map(|x| img.get_pixel(x, 0).0[0]).collect();
+        assert_eq!(row, [255, 170, 85, 0, 255]);
+
+        // Black and white images switch the display back to the normal waveform
+        epd.display(&msb).unwrap();
+        assert_eq!(epd.interface().register(0xE5), None);
+        assert_eq!(epd.interface().visible_image().get_pixel(1, 0).0, [255]);
+    }
+
+    #[test]
+    fn black_white_red_test() {
+        use epd_configs::epd7in5b_v2::EPD_CONFIG;
+        let mut epd = Epd::with_interface(EPD_CONFIG, EpdSimulator::new(&EPD_CONFIG)).unwrap();
+        let size = epd.image_buffer_size();
+        // Black, red, red on black and white in the first four pixels
+        let mut black = vec![0x00; size];
+        let mut red = vec![0x00; size];
+        black[0] = 0b1010_0000;
+        red[0] = 0b0110_0000;
+        epd.display(Frame::BlackWhiteRed {
+            black: &black,
+            red: &red,
+        })
+        .unwrap();
+
+        let img = epd.interface().visible_color_image();
+        let row: Vec<[u8; 3]> = (0..4).map(|x| img.get_pixel(x, 0).0).collect();
+        assert_eq!(row, [[0, 0, 0], [255, 0, 0], [255, 0, 0], [255, 255, 255]]);
+
+        epd.clear().unwrap();
+        let img = epd.interface().visible_color_image();
+        assert!(img.pixels().all(|p| p.0 == [255, 255, 255]));
+
+        let mut epd = simulated_epd();
+        assert!(matches!(
+            epd.display(Frame::BlackWhiteRed {
+                black: &black,
+                red: &red,
+            }),
+            Err(EpdError::Unsupported(_))
+        ));
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn framebuffer_test() {
+        use embedded_graphics_core::pixelcolor::{BinaryColor, Gray2};
+        use embedded_graphics_core::prelude::*;
+        use framebuffer::EpdFrameBuffer;
+
+        let mut epd = simulated_epd();
+        let mut fb = EpdFrameBuffer::<BinaryColor>::new(&EPD_CONFIG);
+        let pixels = [(0, 0), (9, 0), (799, 479), (800, 0), (-1, 0)];
+        fb.draw_iter(pixels.map(|(x, y)| Pixel(Point::new(x, y), BinaryColor::On)))
+            .unwrap();
+        epd.display(&fb).unwrap();
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(0, 0).0, [0]);
+        assert_eq!(img.get_pixel(9, 0).0, [0]);
+        assert_eq!(img.get_pixel(799, 479).0, [0]);
+        assert_eq!(img.pixels().filter(|p| p.0 == [0]).count(), 3);
+
+        let mut fb = EpdFrameBuffer::<Gray2>::new(&EPD_CONFIG);
+        let levels = (0..4).map(|x| Pixel(Point::new(x, 0), Gray2::new(x as u8)));
+        fb.draw_iter(levels).unwrap();
+        epd.display(&fb).unwrap();
+        let img = epd.interface().visible_image();
+        let row: Vec<u8> = (0..5).map(|x| img.get_pixel(x, 0).0[0]).collect();
+        assert_eq!(row, [0, 85, 170, 255, 255]);
+
+        use epd_configs::epd7in5b_v2::EPD_CONFIG as BWR_CONFIG;
+        let mut epd = Epd::with_interface(BWR_CONFIG, EpdSimulator::new(&BWR_CONFIG)).unwrap();
+        let mut fb = EpdFrameBuffer::<Gray2>::new(&BWR_CONFIG);
+        let levels = (0..4).map(|x| Pixel(Point::new(x, 0), Gray2::new(x as u8)));
+        fb.draw_iter(levels).unwrap();
+        epd.display(&fb).unwrap();
+        let img = epd.interface().visible_color_image();
+        let row: Vec<[u8; 3]> = (0..4).map(|x| img.get_pixel(x, 0).0).collect();
+        assert_eq!(row, [[0, 0, 0], [255, 0, 0], [255, 0, 0], [255, 255, 255]]);
+    }
+
+    #[test]
+    fn busy_timeout_test() {
+        let mut epd = simulated_epd();
+        epd.set_busy_timeout(Some(Duration::from_millis(50)));
+        epd.set_busy_poll_interval(Duration::from_millis(10));
+        epd.interface_mut().hold_busy(true);
+        let data = vec![0x00; epd.image_buffer_size()];
+        assert!(matches!(epd.display(&data), Err(EpdError::BusyTimeout)));
+
+        // A display which failed to initialize is dropped without waiting for it again
+        epd.set_busy_timeout(Some(Duration::from_secs(1)));
+        assert!(matches!(epd.init(), Err(EpdError::BusyTimeout)));
+        let start = Instant::now();
+        drop(epd);
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn sleep_test() {
+        let mut epd = simulated_epd();
+        epd.sleep().unwrap();
+        assert_eq!(epd.state(), EpdState::DeepSleep);
+        assert!(epd.interface().is_deep_sleep());
+        assert!(!epd.interface().is_powered_on());
+    }
+
+    #[test]
+    fn wake_from_sleep_test() {
+        let mut epd = simulated_epd();
+        epd.sleep().unwrap();
+        let data = vec![0xFF; epd.image_buffer_size()];
+        epd.display(&data).unwrap();
+        assert_eq!(epd.state(), EpdState::Ready);
+        assert_eq!(epd.interface().refresh_count(), 1);
+
+        epd.power_off().unwrap();
+        assert_eq!(epd.state(), EpdState::PoweredOff);
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().refresh_count(), 2);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_display_test() {
+        use async_epd::AsyncEpd;
+
+        let sim = EpdSimulator::new(&EPD_CONFIG);
+        let mut epd = AsyncEpd::with_interface(EPD_CONFIG, sim).await.unwrap();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0x80;
+        // The other task has to finish while the display is still refreshing
+        let (displayed, ticked) = tokio::join!(
+            async {
+                epd.display(&data).await.unwrap();
+                Instant::now()
+            },
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Instant::now()
+            }
+        );
+        assert!(ticked < displayed);
+        assert_eq!(epd.interface().visible_image().get_pixel(0, 0).0, [0]);
+
+        epd.sleep().await.unwrap();
+        assert_eq!(epd.state(), EpdState::DeepSleep);
+        epd.clear().await.unwrap();
+        assert_eq!(epd.interface().refresh_count(), 2);
+
+        epd.set_busy_timeout(Some(Duration::from_millis(50)));
+        epd.set_busy_poll_interval(Duration::from_millis(10));
+        epd.interface_mut().hold_busy(true);
+        assert!(matches!(
+            epd.display(&data).await,
+            Err(EpdError::BusyTimeout)
+        ));
+        epd.interface_mut().hold_busy(false);
+        epd.sleep().await.unwrap();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_rotation_test() {
+        use async_epd::AsyncEpd;
+
+        let sim = EpdSimulator::new(&EPD_CONFIG);
+        let mut epd = AsyncEpd::with_interface(EPD_CONFIG, sim).await.unwrap();
+        epd.set_rotation(Rotation::Rotate90);
+        assert_eq!(epd.rotation(), Rotation::Rotate90);
+        assert_eq!((epd.width(), epd.height()), (480, 800));
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0xC0;
+        epd.display(&data).await.unwrap();
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(799, 0).0, [0]);
+        assert_eq!(img.get_pixel(799, 1).0, [0]);
+        assert_eq!(img.pixels().filter(|p| p.0 == [0]).count(), 2);
+
+        epd.set_rotation(Rotation::Rotate0);
+        epd.set_mirroring(true, false);
+        epd.display(&data).await.unwrap();
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(798, 0).0, [0]);
+        assert_eq!(img.get_pixel(799, 0).0, [0]);
+        epd.sleep().await.unwrap();
+    }
+
+    #[test]
+    fn model_sequences_test() {
+        use epd_configs::epd2in9d::EPD_CONFIG;
+        let mut epd = Epd::with_interface(EPD_CONFIG, EpdSimulator::new(&EPD_CONFIG)).unwrap();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0x80;
+        epd.display(&data).unwrap();
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(0, 0).0, [0]);
+        assert_eq!(img.get_pixel(1, 0).0, [255]);
+        assert_eq!(
+            epd.interface().register(0x61),
+            Some(&[0x80, 0x01, 0x28][..])
+        );
+
+        epd.sleep().unwrap();
+        assert!(epd.interface().is_deep_sleep());
+        assert_eq!(epd.interface().register(0x50), Some(&[0xf7][..]));
+
+        let config = EpdConfig {
+            busy_polarity: BusyPolarity::ActiveHigh,
+            ..epd_configs::epd5in83_v2::EPD_CONFIG
+        };
+        let mut epd = Epd::with_interface(config.clone(), EpdSimulator::new(&config)).unwrap();
+        epd.set_busy_timeout(Some(Duration::from_millis(50)));
+        epd.clear().unwrap();
+        epd.interface_mut().hold_busy(true);
+        assert!(matches!(epd.clear(), Err(EpdError::BusyTimeout)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_file_test() {
+        let toml = r#"
+            width = 128
+            height = 296
+            inverted = true
+            controller = "uc8151"
+            init_commands = [
+                { send_command = 0x04 },
+                "read_busy",
+                { send_command = 0x00 },
+                { send_data = [0x1f] },
+                { send_command = 0x61 },
+                "send_resolution",
+                { send_command = 0x50 },
+                { send_data = [0x97] },
+            ]
+            sleep_commands = [
+                { send_command = 0x50 },
+                { send_data = [0xf7] },
+                { send_command = 0x02 },
+                "read_busy",
+                { send_command = 0x07 },
+                { send_data = [0xA5] },
+                { delay = 1500 },
+            ]
+        "#;
+        let config = EpdConfig::from_toml(toml).unwrap();
+        assert_eq!(config, epd_configs::epd2in9d::EPD_CONFIG);
+
+        let json = serde_json::to_string(&EPD_CONFIG).unwrap();
+        let config = EpdConfig::from_json(&json).unwrap();
+        assert_eq!(config, EPD_CONFIG);
+        let mut epd = Epd::with_interface(config.clone(), EpdSimulator::new(&config)).unwrap();
+        epd.clear().unwrap();
+
+        assert!(matches!(
+            EpdConfig::from_json(r#"{"width": 122, "height": 250}"#),
+            Err(EpdError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn action_script_test() {
+        let config = EpdConfig {
+            init_commands: Cow::Borrowed(&[
+                Action::HardwareReset {
+                    before_ms: 1,
+                    low_ms: 1,
+                    after_ms: 1,
+                },
+                Action::SetPower(true),
+                Action::WaitBusyPin(true),
+                Action::SendCommand(0x06),
+                Action::Repeat {
+                    count: 3,
+                    actions: Cow::Borrowed(&[Action::SendData(Cow::Borrowed(&[0x17]))]),
+                },
+                Action::SendCommand(0x61),
+                Action::SendResolution,
+                Action::SendCommand(0x04),
+            ]),
+            width: 648,
+            height: 480,
+            ..Default::default()
+        };
+        let mut epd = Epd::with_interface(config.clone(), EpdSimulator::new(&config)).unwrap();
+        let sim = epd.interface();
+        assert_eq!(sim.register(0x06), Some(&[0x17, 0x17, 0x17][..]));
+        assert_eq!(sim.register(0x61), Some(&[0x02, 0x88, 0x01, 0xE0][..]));
+        assert!(sim.is_powered_on());
+
+        epd.set_busy_timeout(Some(Duration::from_millis(50)));
+        epd.interface_mut().hold_busy(true);
+        assert!(matches!(epd.init(), Err(EpdError::BusyTimeout)));
+        epd.interface_mut().hold_busy(false);
+    }
+
+    #[test]
+    fn temperature_test() {
+        let mut epd = simulated_epd();
+        epd.interface_mut().set_temperature(-4.5);
+        assert_eq!(epd.temperature().unwrap(), -4.5);
+
+        // Too cold for the fast waveform, which forces 0xE5 to 0x5A
+        epd.set_refresh_mode(RefreshMode::Fast).unwrap();
+        epd.set_temperature_compensation(Some(TemperatureCompensation::sensor()));
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0xE5), None);
+        epd.interface_mut().set_temperature(21.0);
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0xE5), Some(&[0x5A][..]));
+
+        epd.set_refresh_mode(RefreshMode::Normal).unwrap();
+        epd.set_temperature_compensation(Some(TemperatureCompensation::fixed(-8.0)));
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0xE5), Some(&[-8i8 as u8][..]));
+
+        // Turning compensation off lets the controller read its sensor again
+        epd.set_temperature_compensation(None);
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0xE0), None);
+        assert_eq!(epd.interface().register(0xE5), None);
+    }
+
+    #[test]
+    fn rotation_test() {
+        let mut epd = simulated_epd();
+        let black_pixels = |epd: &Epd<EpdSimulator>| -> Vec<(u32, u32)> {
+            let img = epd.interface().visible_image();
+            img.enumerate_pixels()
+                .filter(|(_, _, p)| p.0 == [0])
+                .map(|(x, y, _)| (x, y))
+                .collect()
+        };
+
+        // Upside down through the scan direction
+        epd.set_rotation(Rotation::Rotate180);
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0xC0;
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().register(0x00), Some(&[0x13][..]));
+        assert_eq!(black_pixels(&epd), [(798, 479), (799, 479)]);
+
+        // Transposed in software
+        epd.set_rotation(Rotation::Rotate90);
+        assert_eq!((epd.width(), epd.height()), (480, 800));
+        epd.display(&data).unwrap();
+        assert_eq!(black_pixels(&epd), [(799, 0), (799, 1)]);
+        epd.display_region(0, 2, 1, 1, &[0x80]).unwrap();
+        assert_eq!(black_pixels(&epd), [(797, 0), (799, 0), (799, 1)]);
+
+        epd.set_rotation(Rotation::Rotate0);
+        epd.set_mirroring(true, false);
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().register(0x00), Some(&[0x1b][..]));
+        assert_eq!(black_pixels(&epd), [(798, 0), (799, 0)]);
+    }
+
+    #[test]
+    fn frame_cache_test() {
+        let mut epd = simulated_epd();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0xF0;
+        epd.display(&data).unwrap();
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().refresh_count(), 1);
+
+        // A small change is refreshed partially
+        epd.set_auto_partial(Some(0.01)).unwrap();
+        data[EPD_CONFIG.width / 8 * 10 + 2] = 0x01;
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().refresh_count(), 2);
+        assert_eq!(
+            epd.interface().register(0x90),
+            Some(&[0, 16, 0, 23, 0, 10, 0, 10, 0x01][..])
+        );
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(3, 0).0, [0]);
+        assert_eq!(img.get_pixel(23, 10).0, [0]);
+
+        // The cache carries over to another run
+        let file = TempFile::new("frame_cache_test.bin");
+        epd.frame_cache().unwrap().save(&file.0).unwrap();
+        let mut epd = simulated_epd();
+        epd.set_frame_cache(FrameCache::load(&file.0).unwrap());
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().refresh_count(), 0);
+        data[0] = 0x00;
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().refresh_count(), 1);
+    }
+
+    #[test]
+    fn ghosting_policy_test() {
+        let mut epd = simulated_epd();
+        epd.set_ghosting_policy(GhostingPolicy {
+            max_partial_refreshes: Some(1),
+            ..Default::default()
+        });
+        epd.clear().unwrap();
+        epd.display_region(0, 0, 8, 1, &[0xFF]).unwrap();
+        assert_eq!(epd.interface().register(0xE5), Some(&[0x6E][..]));
+        // The second partial refresh is replaced with a normal one
+        epd.display_region(8, 0, 8, 1, &[0xFF]).unwrap();
+        assert_eq!(epd.interface().register(0xE5), None);
+        assert_eq!(epd.interface().refresh_count(), 3);
+
+        // Deep cleaning shows the image again afterwards
+        epd.deep_clean().unwrap();
+        assert_eq!(epd.interface().refresh_count(), 4 + 2 * DEEP_CLEAN_CYCLES);
+        let img = epd.interface().visible_image();
+        assert_eq!(img.get_pixel(15, 0).0, [0]);
+        assert_eq!(img.get_pixel(16, 0).0, [255]);
+    }
+
+    #[test]
+    fn idle_policy_test() {
+        let mut epd = simulated_epd();
+        epd.set_idle_policy(Some(IdlePolicy {
+            timeout: Duration::from_millis(50),
+            action: IdleAction::DeepSleep,
+            cut_power: true,
+        }));
+        assert!(!epd.poll_idle().unwrap());
+        sleep(Duration::from_millis(60));
+        assert!(epd.poll_idle().unwrap());
+        assert_eq!(epd.state(), EpdState::PowerCut);
+        assert!(!epd.interface().is_pwr_high());
+        assert_eq!(epd.idle_deadline(), None);
+
+        epd.clear().unwrap();
+        assert!(epd.interface().is_pwr_high());
+        assert_eq!(epd.state(), EpdState::Ready);
+
+        epd.set_idle_policy(Some(IdlePolicy::power_off(Duration::from_millis(10))));
+        let epd = Arc::new(Mutex::new(epd));
+        let watchdog = Epd::watch_idle(&epd);
+        sleep(Duration::from_millis(300));
+        assert_eq!(epd.lock().unwrap().state(), EpdState::PoweredOff);
+        drop(epd);
+        watchdog.join().unwrap();
+
+        // A display which is initialized again by its next operation has nothing to power off
+        let mut epd = simulated_epd();
+        epd.set_idle_policy(Some(IdlePolicy::power_off(Duration::from_millis(10))));
+        epd.set_rotation(Rotation::Rotate90);
+        assert_eq!(epd.state(), EpdState::Uninitialized);
+        sleep(Duration::from_millis(20));
+        assert!(!epd.poll_idle().unwrap());
+        assert_eq!(epd.idle_deadline(), None);
+    }
+
+    #[test]
+    fn status_revision_test() {
+        let mut epd = simulated_epd();
+        let revision = Revision {
+            product: 0x0A0B0C,
+            lut: 0x123456,
+            chip: 0x0C,
+        };
+        epd.interface_mut().set_revision(revision);
+        assert_eq!(epd.revision().unwrap(), revision);
+        assert_eq!(Revision::from_bytes(revision.to_bytes()), revision);
+
+        let status = epd.status().unwrap();
+        assert!(status.powered_on && !status.busy && !status.partial);
+        assert_eq!(Status::from_bits(status.bits()), status);
+        epd.power_off().unwrap();
+        assert!(epd.status().unwrap().powered_off);
+        // A sleeping display is woken up to answer
+        epd.sleep().unwrap();
+        assert!(epd.status().unwrap().powered_on);
+
+        epd.interface_mut().set_revision(Revision::default());
+        assert!(matches!(epd.revision(), Err(EpdError::NoResponse)));
+    }
+
+    #[test]
+    fn rpi_bus_test() {
+        use rpi_helper::{BusClaim, BusLock};
+        use std::sync::mpsc;
+
+        // Displays on a shared bus can be driven from different threads
+        fn shareable<T: Send + Sync>() {}
+        fn sendable<T: Send>() {}
+        shareable::<rpi_helper::RpiBus>();
+        sendable::<Epd<RpiGpio>>();
+
+        /// Takes the bus from another thread and reports once it has it.
+        fn claim_in_thread(lock: &Arc<BusLock>) -> (mpsc::Receiver<()>, JoinHandle<()>) {
+            let (sender, receiver) = mpsc::channel();
+            let mut claim = BusClaim::new(lock.clone());
+            let thread = thread::spawn(move || {
+                claim.set_cs(false, || {});
+                sender.send(()).unwrap();
+                claim.set_cs(true, || {});
+            });
+            (receiver, thread)
+        }
+
+        // The other display waits while CS is low
+        let lock = Arc::new(BusLock::default());
+        let mut claim = BusClaim::new(lock.clone());
+        claim.set_cs(false, || {});
+        let (claimed, thread) = claim_in_thread(&lock);
+        assert!(claimed.recv_timeout(Duration::from_millis(50)).is_err());
+        claim.set_cs(true, || {});
+        claimed.recv_timeout(Duration::from_secs(1)).unwrap();
+        thread.join().unwrap();
+
+        /// A display on the shared bus whose SPI writes can be made to fail.
+        struct SharedDevice {
+            claim: BusClaim,
+            fail_writes: bool,
+        }
+
+        impl EpdInterface for SharedDevice {
+            fn set_rst(&mut self, _: bool) -> Result<(), EpdError> {
+                Ok(())
+            }
+
+            fn set_dc(&mut self, _: bool) -> Result<(), EpdError> {
+                Ok(())
+            }
+
+            fn set_cs(&mut self, high: bool) -> Result<(), EpdError> {
+                self.claim.set_cs(high, || {});
+                Ok(())
+            }
+
+            fn set_pwr(&mut self, _: bool) -> Result<(), EpdError> {
+                Ok(())
+            }
+
+            fn spi_write(&mut self, _: &[u8]) -> Result<(), EpdError> {
+                if self.fail_writes {
+                    return Err(EpdError::Spi("write failed".into()));
+                }
+                Ok(())
+            }
+
+            fn busy_high(&mut self) -> Result<bool, EpdError> {
+                Ok(true)
+            }
+        }
+
+        // A failed write releases the bus while the display is still in use
+        let device = SharedDevice {
+            claim: BusClaim::new(lock.clone()),
+            fail_writes: false,
+        };
+        let mut epd = Epd::with_interface(EPD_CONFIG, device).unwrap();
+        epd.interface_mut().fail_writes = true;
+        assert!(matches!(epd.clear(), Err(EpdError::Spi(_))));
+        let (claimed, thread) = claim_in_thread(&lock);
+        claimed.recv_timeout(Duration::from_secs(1)).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn recorder_test() {
+        let file = TempFile::new("recorder_test.rec");
+        let mut epd = simulated_epd();
+        epd.set_recorder(Some(recorder::Recorder::create(&file.0).unwrap()));
+        epd.init().unwrap();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0xF0;
+        epd.display(&data).unwrap();
+        epd.status().unwrap();
+        let image = epd.interface().visible_image();
+        let sent = epd.ram_data(&data).into_owned();
+        drop(epd);
+
+        let records = recorder::load(&file.0).unwrap();
+        let events: Vec<_> = records.iter().map(|record| &record.event).collect();
+        assert_eq!(events[0], &Event::Reset(RESET_TIMINGS));
+        assert!(events.contains(&&Event::Command(0x12)));
+        assert!(events.contains(&&Event::Data(sent)));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::Read(status) if status.len() == 1)));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::Busy { .. })));
+        assert!(records.windows(2).all(|w| w[0].time <= w[1].time));
+        for record in &records {
+            assert_eq!(
+                &record.to_string().parse::<recorder::Record>().unwrap(),
+                record
+            );
+        }
+        assert!(matches!(
+            "0.5 CMD 1234".parse::<recorder::Record>(),
+            Err(EpdError::Io(_))
+        ));
+
+        // Replaying the recording on another display shows the same image
+        let mut sim = EpdSimulator::new(&EPD_CONFIG);
+        recorder::replay(&mut sim, &records).unwrap();
+        assert_eq!(sim.visible_image(), image);
+    }
+
+    #[test]
+    fn registers_test() {
+        let mut epd = simulated_epd();
+        assert_eq!(epd.panel_setting(), Some(PanelSetting::from_bits(0x1F)));
+        assert_eq!(PanelSetting::from_bits(0x1F).bits(), 0x1F);
+
+        epd.set_border(Some(BorderColor::White)).unwrap();
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0x50), Some(&[0x10, 0x07][..]));
+        epd.set_border(Some(BorderColor::Black)).unwrap();
+        epd.set_vcom(Some(-1.5)).unwrap();
+        epd.set_panel_setting(Some(PanelSetting {
+            booster_on: false,
+            ..epd.panel_setting().unwrap()
+        }))
+        .unwrap();
+        epd.clear().unwrap();
+        let sim = epd.interface();
+        assert_eq!(sim.register(0x50), Some(&[0x20, 0x07][..]));
+        assert_eq!(sim.register(0x82), Some(&[0x1C][..]));
+        assert_eq!(sim.register(0x00), Some(&[0x1D][..]));
+
+        // The partial init commands let the border float unless it is set
+        epd.set_border(None).unwrap();
+        epd.set_auto_partial(Some(1.0)).unwrap();
+        let mut data = vec![0x00; epd.image_buffer_size()];
+        data[0] = 0xFF;
+        epd.display(&data).unwrap();
+        assert_eq!(epd.interface().register(0x50), Some(&[0xA9, 0x07][..]));
+        assert_eq!(epd.interface().register(0x82), Some(&[0x1C][..]));
+
+        assert!(matches!(
+            epd.set_border(Some(BorderColor::Red)),
+            Err(EpdError::Unsupported(_))
+        ));
+        assert!(matches!(
+            epd.set_vcom(Some(-4.5)),
+            Err(EpdError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            epd.set_resolution(1024, 480),
+            Err(EpdError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            epd.set_resolution(404, 240),
+            Err(EpdError::InvalidConfig(_))
+        ));
+        epd.set_resolution(400, 240).unwrap();
+        assert_eq!((epd.width(), epd.height()), (400, 240));
+        epd.init().unwrap();
+        assert_eq!(
+            epd.interface().register(0x61),
+            Some(&[0x01, 0x90, 0x00, 0xF0][..])
+        );
+
+        // Init commands which write the resolution as raw bytes get the new one as well
+        let mut config = EPD_CONFIG;
+        config.init_commands = config
+            .init_commands
+            .iter()
+            .map(|action| match action {
+                Action::SendResolution => {
+                    Action::SendData(Cow::Borrowed(&[0x03, 0x20, 0x01, 0xE0]))
+                }
+                action => action.clone(),
+            })
+            .collect();
+        let mut epd = Epd::with_interface(config.clone(), EpdSimulator::new(&config)).unwrap();
+        epd.set_resolution(400, 240).unwrap();
+        epd.init().unwrap();
+        assert_eq!(
+            epd.interface().register(0x61),
+            Some(&[0x01, 0x90, 0x00, 0xF0][..])
+        );
+
+        use epd_configs::epd7in5b_v2::EPD_CONFIG as BWR_CONFIG;
+        let mut epd = Epd::with_interface(BWR_CONFIG, EpdSimulator::new(&BWR_CONFIG)).unwrap();
+        epd.set_border(Some(BorderColor::Red)).unwrap();
+        epd.clear().unwrap();
+        assert_eq!(epd.interface().register(0x50), Some(&[0x21, 0x07][..]));
+    }
+
+    #[cfg(feature = "linux")]
+    #[test]
+    fn linux_config_test() {
+        use linux::{GpioLine, LinuxGpio, LinuxHardwareConfig};
+        fn sendable<T: Send>() {}
+        sendable::<Epd<LinuxGpio>>();
+
+        let config = LinuxHardwareConfig::new(
+            "/dev/spidev1.0",
+            GpioLine::new("/dev/gpiochip1", 5),
+            GpioLine::new("/dev/gpiochip1", 6),
+            GpioLine::new("/dev/gpiochip1", 7),
+        );
+        assert_eq!(config.spi_clock_speed, 4_000_000);
+        assert!(config.cs.is_none() && config.pwr.is_none() && !config.three_wire);
+        // Missing devices are reported instead of panicking
+        let missing = LinuxHardwareConfig::new(
+            "/dev/spidev-missing",
+            GpioLine::new("/dev/gpiochip-missing", 5),
+            GpioLine::new("/dev/gpiochip-missing", 6),
+            GpioLine::new("/dev/gpiochip-missing", 7),
+        );
+        assert!(matches!(
+            LinuxGpio::new(&missing),
+            Err(EpdError::GpioUnavailable(_))
+        ));
     }
 }
@@ -0,0 +1,289 @@
+//! Contains an implementation of [`EpdInterface`] for any Linux board, built on the `spidev` and
+//! GPIO character device interfaces of the kernel.
+//!
+//! This module is only available with the `linux` feature. Use it for boards which are not
+//! supported by [`rpi_helper`](crate::rpi_helper), like the Rockchip and Allwinner based ones.
+use crate::interface::EpdInterface;
+use crate::rpi_helper::SPI_CLOCK_SPEED;
+use crate::EpdError;
+use gpio_cdev::{Chip, EventRequestFlags, LineEventHandle, LineHandle, LineRequestFlags};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use spidev::{SpiModeFlags, Spidev, SpidevOptions};
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// Name under which the GPIO lines are requested from the kernel.
+const CONSUMER: &str = "waveshare-rpi";
+
+/// A GPIO line, addressed by the character device of its GPIO chip (e.g. `/dev/gpiochip0`) and
+/// its offset on the chip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpioLine {
+    pub chip: PathBuf,
+    pub offset: u32,
+}
+
+impl GpioLine {
+    /// Creates a new `GpioLine` for line `offset` of the GPIO chip at `chip`.
+    pub fn new(chip: impl Into<PathBuf>, offset: u32) -> Self {
+        Self {
+            chip: chip.into(),
+            offset,
+        }
+    }
+}
+
+/// Describes how the display is wired to a Linux board.
+///
+/// # Available Options
+///
+/// | Option name | Type | Description | Default value |
+/// |---|---|---|---|
+/// | `spi_device` | `PathBuf` | The `spidev` device the display is connected to, e.g. `/dev/spidev0.0`. | |
+/// | `rst` | [`GpioLine`] | Line connected to RST. | |
+/// | `dc` | [`GpioLine`] | Line connected to DC. | |
+/// | `cs` | `Option<GpioLine>` | Line driven as CS. Set to `None` to leave chip select to the SPI controller. | `None` |
+/// | `busy` | [`GpioLine`] | Line connected to BUSY. | |
+/// | `pwr` | `Option<GpioLine>` | Line which switches the display's power. | `None` |
+/// | `spi_clock_speed` | `u32` | SPI clock speed in Hz. | 4000000 |
+/// | `three_wire` | `bool` | Whether to run the SPI controller in 3-wire mode, which reads the display's answers on MOSI. | `false` |
+///
+/// # Examples
+///
+/// ```no_run
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+/// use waveshare_rpi::linux::{GpioLine, LinuxGpio, LinuxHardwareConfig};
+///
+/// let hardware_config = LinuxHardwareConfig::new(
+///     "/dev/spidev3.0",
+///     GpioLine::new("/dev/gpiochip3", 17),
+///     GpioLine::new("/dev/gpiochip3", 18),
+///     GpioLine::new("/dev/gpiochip3", 19),
+/// );
+/// let interface = LinuxGpio::new(&hardware_config).unwrap();
+/// let mut epd = Epd::with_interface(EPD_CONFIG, interface).unwrap();
+/// epd.clear().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinuxHardwareConfig {
+    pub spi_device: PathBuf,
+    pub rst: GpioLine,
+    pub dc: GpioLine,
+    pub cs: Option<GpioLine>,
+    pub busy: GpioLine,
+    pub pwr: Option<GpioLine>,
+    pub spi_clock_speed: u32,
+    pub three_wire: bool,
+}
+
+impl LinuxHardwareConfig {
+    /// Creates a new `LinuxHardwareConfig` with the required lines and default values for the
+    /// other options.
+    pub fn new(
+        spi_device: impl Into<PathBuf>,
+        rst: GpioLine,
+        dc: GpioLine,
+        busy: GpioLine,
+    ) -> Self {
+        Self {
+            spi_device: spi_device.into(),
+            rst,
+            dc,
+            cs: None,
+            busy,
+            pwr: None,
+            spi_clock_speed: SPI_CLOCK_SPEED,
+            three_wire: false,
+        }
+    }
+}
+
+/// A struct that contains all the interfaces required to interact with an E-Paper Display on a
+/// Linux board.
+///
+/// Reading from the display is only supported in 3-wire mode, as the display answers on the data
+/// line connected to MOSI.
+pub struct LinuxGpio {
+    spi: Spidev,
+    rst: LineHandle,
+    dc: LineHandle,
+    cs: Option<LineHandle>,
+    busy: BusyLine,
+    pwr: Option<LineHandle>,
+    three_wire: bool,
+}
+
+/// The BUSY line, which reports its edges if the GPIO chip supports interrupts on it.
+enum BusyLine {
+    Events(LineEventHandle),
+    Level(LineHandle),
+}
+
+impl BusyLine {
+    fn request(line: &GpioLine) -> Result<Self, EpdError> {
+        let events = Chip::new(&line.chip)?.get_line(line.offset)?.events(
+            LineRequestFlags::INPUT,
+            EventRequestFlags::BOTH_EDGES,
+            CONSUMER,
+        );
+        match events {
+            Ok(events) => Ok(BusyLine::Events(events)),
+            Err(e) => match request_error(line, e) {
+                EpdError::PinBusy(pin) => Err(EpdError::PinBusy(pin)),
+                e => {
+                    log::warn!(
+                        "Unable to use interrupts on the busy line, falling back to polling: {e}"
+                    );
+                    Ok(BusyLine::Level(request_line(
+                        line,
+                        LineRequestFlags::INPUT,
+                        0,
+                    )?))
+                }
+            },
+        }
+    }
+}
+
+impl LinuxGpio {
+    /// Opens the SPI device and requests the GPIO lines described by `config`. The PWR line is
+    /// driven high to power the display.
+    /// Returns `Err(EpdError::PinBusy)` if one of the lines is in use by another consumer.
+    pub fn new(config: &LinuxHardwareConfig) -> Result<Self, EpdError> {
+        let pwr = config
+            .pwr
+            .as_ref()
+            .map(|line| request_line(line, LineRequestFlags::OUTPUT, 1))
+            .transpose()?;
+        let rst = request_line(&config.rst, LineRequestFlags::OUTPUT, 0)?;
+        let dc = request_line(&config.dc, LineRequestFlags::OUTPUT, 0)?;
+        let cs = config
+            .cs
+            .as_ref()
+            .map(|line| request_line(line, LineRequestFlags::OUTPUT, 1))
+            .transpose()?;
+        let busy = BusyLine::request(&config.busy)?;
+
+        let mut mode = SpiModeFlags::SPI_MODE_0;
+        if cs.is_some() {
+            mode |= SpiModeFlags::SPI_NO_CS;
+        }
+        if config.three_wire {
+            mode |= SpiModeFlags::SPI_3WIRE;
+        }
+        let mut spi = Spidev::open(&config.spi_device).map_err(spi_error)?;
+        spi.configure(
+            &SpidevOptions::new()
+                .bits_per_word(8)
+                .max_speed_hz(config.spi_clock_speed)
+                .mode(mode)
+                .build(),
+        )
+        .map_err(spi_error)?;
+
+        Ok(LinuxGpio {
+            spi,
+            rst,
+            dc,
+            cs,
+            busy,
+            pwr,
+            three_wire: config.three_wire,
+        })
+    }
+}
+
+fn request_line(
+    line: &GpioLine,
+    flags: LineRequestFlags,
+    default: u8,
+) -> Result<LineHandle, EpdError> {
+    Chip::new(&line.chip)?
+        .get_line(line.offset)?
+        .request(flags, default, CONSUMER)
+        .map_err(|e| request_error(line, e))
+}
+
+/// Reports lines which are requested by another consumer as [`EpdError::PinBusy`].
+fn request_error(line: &GpioLine, e: gpio_cdev::Error) -> EpdError {
+    match e.source().and_then(|s| s.downcast_ref::<Errno>()) {
+        Some(Errno::EBUSY) => EpdError::PinBusy(line.offset),
+        _ => e.into(),
+    }
+}
+
+fn spi_error(e: std::io::Error) -> EpdError {
+    EpdError::Spi(Box::new(e))
+}
+
+impl EpdInterface for LinuxGpio {
+    fn set_rst(&mut self, high: bool) -> Result<(), EpdError> {
+        Ok(self.rst.set_value(high.into())?)
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<(), EpdError> {
+        Ok(self.dc.set_value(high.into())?)
+    }
+
+    fn set_cs(&mut self, high: bool) -> Result<(), EpdError> {
+        if let Some(cs) = &self.cs {
+            cs.set_value(high.into())?;
+        }
+        Ok(())
+    }
+
+    fn set_pwr(&mut self, high: bool) -> Result<(), EpdError> {
+        if let Some(pwr) = &self.pwr {
+            pwr.set_value(high.into())?;
+        }
+        Ok(())
+    }
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<(), EpdError> {
+        self.spi.write_all(data).map_err(spi_error)
+    }
+
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<(), EpdError> {
+        if !self.three_wire {
+            return Err(EpdError::Unsupported("reading without 3-wire SPI"));
+        }
+        self.spi.read_exact(buffer).map_err(spi_error)
+    }
+
+    fn busy_high(&mut self) -> Result<bool, EpdError> {
+        let value = match &self.busy {
+            BusyLine::Events(events) => events.get_value()?,
+            BusyLine::Level(level) => level.get_value()?,
+        };
+        Ok(value != 0)
+    }
+
+    fn wait_for_busy_edge(&mut self, timeout: Duration) -> Result<(), EpdError> {
+        let BusyLine::Events(events) = &mut self.busy else {
+            sleep(timeout);
+            return Ok(());
+        };
+        // Edges which happened since the last wait are queued by the kernel, so a level change
+        // between reading the line and calling this function is not missed
+        let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+        let ready = poll(&mut [PollFd::new(events, PollFlags::POLLIN)], millis)
+            .map_err(|e| EpdError::Io(e.into()))?;
+        if ready > 0 {
+            events.get_event()?;
+        }
+        Ok(())
+    }
+}
+
+// Power down the EPD when dropping
+impl Drop for LinuxGpio {
+    fn drop(&mut self) {
+        let _ = self.rst.set_value(0);
+        let _ = self.dc.set_value(0);
+        let _ = self.set_pwr(false);
+    }
+}
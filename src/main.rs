@@ -1,6 +1,10 @@
 use clap::Parser;
+use std::error::Error;
 use waveshare_rpi::converter::{image_to_epd, text_to_epd, EpdImageOptions};
-use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+use waveshare_rpi::epd_configs::{epd7in5_v2::EPD_CONFIG, EpdConfig};
+use waveshare_rpi::recorder::{self, Recorder};
+use waveshare_rpi::rpi_helper::RpiGpio;
+use waveshare_rpi::{Epd, EpdError, FrameCache};
 
 /// Program to update a Waveshare 7.5" e-ink display
 #[derive(Parser, Debug)]
@@ -17,32 +21,99 @@ struct Args {
     /// Clear the display
     #[arg(short, long)]
     clear: bool,
+
+    /// Path of a file which remembers the image on the display between runs, so unchanged images
+    /// are not refreshed again
+    #[arg(long)]
+    cache: Option<String>,
+
+    /// Use a partial refresh if the changes cover at most this percentage of the display
+    #[arg(short, long, value_name = "PERCENT")]
+    partial: Option<f32>,
+
+    /// Path of a file to record the traffic sent to the display to
+    #[arg(long, value_name = "FILE")]
+    record: Option<String>,
+
+    /// Path of a recording to play back on the display
+    #[arg(long, value_name = "FILE")]
+    replay: Option<String>,
+
+    /// Path of a TOML or JSON file describing the display model to use instead of the 7.5" V2
+    #[cfg(feature = "serde")]
+    #[arg(short, long)]
+    model: Option<String>,
+}
+
+#[cfg_attr(not(feature = "serde"), allow(unused_variables))]
+fn epd_config(args: &Args) -> Result<EpdConfig, EpdError> {
+    #[cfg(feature = "serde")]
+    if let Some(path) = &args.model {
+        return EpdConfig::from_file(path);
+    }
+    Ok(EPD_CONFIG)
 }
 
-fn main() {
+fn open_epd(config: EpdConfig, args: &Args) -> Result<Epd<RpiGpio>, EpdError> {
+    let mut epd = Epd::new(config)?;
+    epd.set_auto_partial(args.partial.map(|percent| percent / 100.0))?;
+    if let Some(path) = &args.record {
+        epd.set_recorder(Some(Recorder::create(path)?));
+        epd.init()?;
+    }
+    if let Some(path) = &args.cache {
+        match FrameCache::load(path) {
+            Ok(cache) => epd.set_frame_cache(cache),
+            Err(EpdError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Ignoring frame cache {path}: {e}"),
+        }
+    }
+    Ok(epd)
+}
+
+fn save_cache(epd: &Epd<RpiGpio>, args: &Args) -> Result<(), EpdError> {
+    match (&args.cache, epd.frame_cache()) {
+        (Some(path), Some(cache)) => cache.save(path),
+        _ => Ok(()),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    simple_logger::SimpleLogger::new().env().init()?;
+    let config = epd_config(&args)?;
+
+    if let Some(path) = &args.replay {
+        let records = recorder::load(path)?;
+        recorder::replay(&mut RpiGpio::new()?, &records)?;
+        return Ok(());
+    }
 
-    if let Some(filepath) = args.image {
+    if let Some(filepath) = &args.image {
         let mut image_options = EpdImageOptions::new();
-        image_options.load_epd_config(EPD_CONFIG);
-        let data = image_to_epd(&filepath, image_options).unwrap();
-        let mut epd = Epd::new(EPD_CONFIG);
-        epd.display(&data).unwrap();
-        return;
+        image_options.load_epd_config(config.clone());
+        let data = image_to_epd(filepath, image_options)?;
+        let mut epd = open_epd(config, &args)?;
+        epd.display(&data)?;
+        save_cache(&epd, &args)?;
+        return Ok(());
     }
 
-    if let Some(text) = args.text {
-        let data = text_to_epd(&text, 24.0, EPD_CONFIG.width, EPD_CONFIG.height).unwrap();
-        let mut epd = Epd::new(EPD_CONFIG);
-        epd.display(&data).unwrap();
-        return;
+    if let Some(text) = &args.text {
+        let data = text_to_epd(text, 24.0, config.width, config.height)?;
+        let mut epd = open_epd(config, &args)?;
+        epd.display(&data)?;
+        save_cache(&epd, &args)?;
+        return Ok(());
     }
 
     if args.clear {
-        let mut epd = Epd::new(EPD_CONFIG);
-        epd.clear();
-        return;
+        let mut epd = open_epd(config, &args)?;
+        epd.clear()?;
+        save_cache(&epd, &args)?;
+        return Ok(());
     }
 
     println!("No image or text specified. Use --help for usage information.");
+    Ok(())
 }
@@ -0,0 +1,330 @@
+//! Contains a recorder of the traffic between [`Epd`](crate::Epd) and the display, and a function
+//! to replay such a recording on any [`EpdInterface`].
+//!
+//! A recording is a text file with one event per line, preceded by the number of seconds since
+//! the recording started. Lines starting with `#` are comments.
+//!
+//! | Event | Line | Meaning |
+//! |---|---|---|
+//! | [`Event::Reset`] | `0.000000 RESET 20 2 20` | RST held high, low and high for the given number of milliseconds |
+//! | [`Event::Command`] | `0.040210 CMD 01` | Command byte, in hex |
+//! | [`Event::Data`] | `0.040325 DATA 07073F3F` | Data bytes, in hex |
+//! | [`Event::Read`] | `0.050012 READ 02` | Bytes read from the display, in hex |
+//! | [`Event::Busy`] | `0.061200 BUSY 1 0.101520` | Wait for the BUSY pin to be high (`1`) or low (`0`), and how many seconds it took |
+//! | [`Event::Power`] | `9.870000 PWR 0` | PWR pin driven high (`1`) or low (`0`) |
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+//! use waveshare_rpi::recorder::{self, Recorder};
+//! use waveshare_rpi::rpi_helper::RpiGpio;
+//!
+//! # fn main() -> Result<(), waveshare_rpi::EpdError> {
+//! let mut epd = Epd::new(EPD_CONFIG)?;
+//! epd.set_recorder(Some(Recorder::create("clear.rec")?));
+//! epd.clear()?;
+//! drop(epd);
+//!
+//! let mut gpio = RpiGpio::new()?;
+//! recorder::replay(&mut gpio, &recorder::load("clear.rec")?)?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::interface::EpdInterface;
+use crate::{EpdError, BUSY_POLL_INTERVAL, BUSY_TIMEOUT, DATA_BUFFER_SIZE};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Something [`Epd`](crate::Epd) did on the display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Holds the RST pin high, low and high again for the given number of milliseconds.
+    Reset([u64; 3]),
+    /// Sends a command byte.
+    Command(u8),
+    /// Sends data bytes.
+    Data(Vec<u8>),
+    /// Reads bytes sent by the display.
+    Read(Vec<u8>),
+    /// Waits for the BUSY pin to be at `level`, which took `waited`.
+    Busy { level: bool, waited: Duration },
+    /// Drives the PWR pin.
+    Power(bool),
+}
+
+impl Event {
+    /// Returns how long the event kept the display busy.
+    fn duration(&self) -> Duration {
+        match self {
+            Event::Reset(timings) => Duration::from_millis(timings.iter().sum()),
+            Event::Busy { waited, .. } => *waited,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Reset([before, low, after]) => write!(f, "RESET {before} {low} {after}"),
+            Event::Command(command) => write!(f, "CMD {command:02X}"),
+            Event::Data(data) => write!(f, "DATA {}", Hex(data)),
+            Event::Read(data) => write!(f, "READ {}", Hex(data)),
+            Event::Busy { level, waited } => {
+                write!(f, "BUSY {} {:.6}", u8::from(*level), waited.as_secs_f64())
+            }
+            Event::Power(high) => write!(f, "PWR {}", u8::from(*high)),
+        }
+    }
+}
+
+impl FromStr for Event {
+    type Err = EpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let event = match fields.next().ok_or_else(|| invalid(s))? {
+            "RESET" => {
+                let mut timings = [0; 3];
+                for timing in &mut timings {
+                    *timing = parse_field(fields.next(), s)?;
+                }
+                Event::Reset(timings)
+            }
+            "CMD" => Event::Command(
+                u8::from_str_radix(fields.next().unwrap_or_default(), 16)
+                    .map_err(|_| invalid(s))?,
+            ),
+            "DATA" => Event::Data(parse_hex(fields.next(), s)?),
+            "READ" => Event::Read(parse_hex(fields.next(), s)?),
+            "BUSY" => Event::Busy {
+                level: parse_level(fields.next(), s)?,
+                waited: parse_seconds(fields.next(), s)?,
+            },
+            "PWR" => Event::Power(parse_level(fields.next(), s)?),
+            _ => return Err(invalid(s)),
+        };
+        match fields.next() {
+            Some(_) => Err(invalid(s)),
+            None => Ok(event),
+        }
+    }
+}
+
+/// An [`Event`] and when it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Time since the recording started.
+    pub time: Duration,
+    pub event: Event,
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.6} {}", self.time.as_secs_f64(), self.event)
+    }
+}
+
+impl FromStr for Record {
+    type Err = EpdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, event) = s.trim().split_once(' ').ok_or_else(|| invalid(s))?;
+        Ok(Record {
+            time: parse_seconds(Some(time), s)?,
+            event: event.parse()?,
+        })
+    }
+}
+
+/// Writes the events of an [`Epd`](crate::Epd) to a recording. See
+/// [`Epd::set_recorder`](crate::Epd::set_recorder).
+///
+/// Every event is flushed as soon as it is recorded, so the recording is complete up to the last
+/// event if the program crashes.
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Creates a recorder which writes to `out`.
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            start: Instant::now(),
+        }
+    }
+
+    /// Creates a recorder which writes to the file at `path`, replacing it if it exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, EpdError> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "# waveshare-rpi recording")?;
+        Ok(Self::new(out))
+    }
+
+    /// Returns the time since the recorder was created.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Writes `event`, which happened `time` after the recorder was created.
+    pub(crate) fn record(&mut self, time: Duration, event: Event) -> io::Result<()> {
+        writeln!(self.out, "{}", Record { time, event })?;
+        self.out.flush()
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads the records of a recording from `reader`.
+/// Returns `Err(EpdError::Io)` if a line cannot be read or is not a record.
+pub fn read_records<R: BufRead>(reader: R) -> Result<Vec<Record>, EpdError> {
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            records.push(line.parse()?);
+        }
+    }
+    Ok(records)
+}
+
+/// Reads the records of the recording in the file at `path`. See [`read_records`].
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Record>, EpdError> {
+    read_records(BufReader::new(File::open(path)?))
+}
+
+/// Plays `records` back on `interface`, doing what [`Epd`](crate::Epd) did when they were
+/// recorded, including the pauses between the events.
+///
+/// Busy waits wait for the BUSY pin of `interface` rather than for the recorded time, and give up
+/// with `Err(EpdError::BusyTimeout)` after 30 seconds. Reads are skipped if `interface` does not
+/// support them.
+pub fn replay<I: EpdInterface>(interface: &mut I, records: &[Record]) -> Result<(), EpdError> {
+    let mut previous_end = None;
+    for record in records {
+        if let Some(end) = previous_end {
+            sleep(record.time.saturating_sub(end));
+        }
+        replay_event(interface, &record.event)?;
+        previous_end = Some(record.time + record.event.duration());
+    }
+    Ok(())
+}
+
+fn replay_event<I: EpdInterface>(interface: &mut I, event: &Event) -> Result<(), EpdError> {
+    match event {
+        Event::Reset(timings) => {
+            for (high, ms) in [true, false, true].into_iter().zip(timings) {
+                interface.set_rst(high)?;
+                sleep(Duration::from_millis(*ms));
+            }
+            Ok(())
+        }
+        Event::Command(command) => {
+            interface.set_dc(false)?;
+            interface.set_cs(false)?;
+            // CS goes high again even if the write fails, so a shared bus is released
+            let result = interface.spi_write(&[*command]);
+            interface.set_cs(true)?;
+            result
+        }
+        Event::Data(data) => {
+            for chunk in data.chunks(DATA_BUFFER_SIZE) {
+                interface.set_dc(true)?;
+                interface.set_cs(false)?;
+                let result = interface.spi_write(chunk);
+                interface.set_cs(true)?;
+                result?;
+            }
+            Ok(())
+        }
+        Event::Read(data) => {
+            let mut buffer = vec![0x00; data.len()];
+            interface.set_dc(true)?;
+            interface.set_cs(false)?;
+            let result = interface.spi_read(&mut buffer);
+            interface.set_cs(true)?;
+            match result {
+                Err(EpdError::Unsupported(_)) => Ok(()),
+                result => result,
+            }
+        }
+        Event::Busy { level, .. } => {
+            let start = Instant::now();
+            while interface.busy_high()? != *level {
+                let elapsed = start.elapsed();
+                if elapsed >= BUSY_TIMEOUT {
+                    return Err(EpdError::BusyTimeout);
+                }
+                interface.wait_for_busy_edge(BUSY_POLL_INTERVAL.min(BUSY_TIMEOUT - elapsed))?;
+            }
+            Ok(())
+        }
+        Event::Power(high) => interface.set_pwr(*high),
+    }
+}
+
+/// Formats bytes as uppercase hex without separators.
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02X}"))
+    }
+}
+
+fn invalid(line: &str) -> EpdError {
+    EpdError::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("not a recorded event: {line}"),
+    ))
+}
+
+fn parse_field<T: FromStr>(field: Option<&str>, line: &str) -> Result<T, EpdError> {
+    field
+        .and_then(|field| field.parse().ok())
+        .ok_or_else(|| invalid(line))
+}
+
+fn parse_level(field: Option<&str>, line: &str) -> Result<bool, EpdError> {
+    match field {
+        Some("0") => Ok(false),
+        Some("1") => Ok(true),
+        _ => Err(invalid(line)),
+    }
+}
+
+fn parse_seconds(field: Option<&str>, line: &str) -> Result<Duration, EpdError> {
+    let seconds: f64 = parse_field(field, line)?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid(line))
+}
+
+fn parse_hex(field: Option<&str>, line: &str) -> Result<Vec<u8>, EpdError> {
+    let hex = field.unwrap_or_default();
+    if !hex.len().is_multiple_of(2) {
+        return Err(invalid(line));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| invalid(line))
+        })
+        .collect()
+}
@@ -0,0 +1,223 @@
+//! Contains typed values for the registers of the display controllers which are usually set by
+//! raw bytes in the init commands of an [`EpdConfig`].
+//!
+//! The values are set with [`Epd::set_panel_setting`](crate::Epd::set_panel_setting),
+//! [`Epd::set_border`](crate::Epd::set_border), [`Epd::set_vcom`](crate::Epd::set_vcom) and
+//! [`Epd::set_resolution`](crate::Epd::set_resolution), which encode them for the
+//! [`Controller`] of the display and write them in place of the bytes of the init commands.
+use crate::epd_configs::{Action, Controller, EpdConfig, PanelColors};
+use std::borrow::Cow;
+
+/// Panel setting register (`0x00`).
+pub(crate) const PANEL_SETTING: u8 = 0x00;
+/// VCOM and data interval setting register (`0x50`), which holds the border selection.
+pub(crate) const VCOM_DATA_INTERVAL: u8 = 0x50;
+/// Resolution setting register (`0x61`).
+pub(crate) const RESOLUTION_SETTING: u8 = 0x61;
+/// VCOM DC setting register (`0x82`).
+pub(crate) const VCOM_DC: u8 = 0x82;
+
+/// Bits of the panel setting shared by the UC81xx controllers.
+const PSR_REG: u8 = 0x20;
+const PSR_KWR: u8 = 0x10;
+pub(crate) const PSR_UD: u8 = 0x08;
+pub(crate) const PSR_SHL: u8 = 0x04;
+const PSR_SHD_N: u8 = 0x02;
+const PSR_RST_N: u8 = 0x01;
+/// Resolution select bits of the UC8151, which are left as set by the init commands.
+const PSR_RES: u8 = 0xC0;
+
+/// The panel setting register (`0x00`), which is laid out the same way by the UC8179 and the
+/// UC8151.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelSetting {
+    /// Whether the waveforms are loaded from the LUT registers instead of the OTP (REG).
+    pub lut_from_register: bool,
+    /// Whether only black and white are driven, rather than black, white and red (KW/R).
+    pub black_white: bool,
+    /// Whether the gates are scanned up, from the first to the last (UD).
+    pub scan_up: bool,
+    /// Whether the sources are shifted right, from the first to the last (SHL).
+    pub shift_right: bool,
+    /// Whether the booster is switched on (SHD_N).
+    pub booster_on: bool,
+}
+
+impl PanelSetting {
+    /// Decodes the byte of the panel setting register.
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            lut_from_register: bits & PSR_REG != 0,
+            black_white: bits & PSR_KWR != 0,
+            scan_up: bits & PSR_UD != 0,
+            shift_right: bits & PSR_SHL != 0,
+            booster_on: bits & PSR_SHD_N != 0,
+        }
+    }
+
+    /// Encodes the setting into the byte of the panel setting register. The soft reset bit
+    /// (RST_N) is always set, as clearing it resets the controller.
+    pub fn bits(self) -> u8 {
+        let flag = |set: bool, bit: u8| if set { bit } else { 0 };
+        flag(self.lut_from_register, PSR_REG)
+            | flag(self.black_white, PSR_KWR)
+            | flag(self.scan_up, PSR_UD)
+            | flag(self.shift_right, PSR_SHL)
+            | flag(self.booster_on, PSR_SHD_N)
+            | PSR_RST_N
+    }
+}
+
+/// Color driven on the border around the active area of the display, selected in the VCOM and
+/// data interval setting register (`0x50`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BorderColor {
+    Black,
+    White,
+    /// Only available on black, white and red displays.
+    Red,
+    /// The border is not driven and keeps its color.
+    Floating,
+}
+
+impl BorderColor {
+    /// Returns the border LUT selection (VBD on the UC8151, BDV on the UC8179). Which LUT drives
+    /// which color depends on whether the panel is in black/white/red mode and on the data
+    /// polarity (DDX\[0\]).
+    fn lut(self, colors: PanelColors, ddx0: bool) -> u8 {
+        match (colors, self) {
+            (PanelColors::BlackWhite, BorderColor::Floating) => 0b00,
+            (PanelColors::BlackWhite, BorderColor::Black) if ddx0 => 0b01,
+            (PanelColors::BlackWhite, _) if ddx0 => 0b10,
+            (PanelColors::BlackWhite, BorderColor::Black) => 0b10,
+            (PanelColors::BlackWhite, _) => 0b01,
+            (PanelColors::BlackWhiteRed, BorderColor::Black) => u8::from(!ddx0),
+            (PanelColors::BlackWhiteRed, BorderColor::White) => u8::from(ddx0),
+            (PanelColors::BlackWhiteRed, BorderColor::Red) => 0b10,
+            (PanelColors::BlackWhiteRed, BorderColor::Floating) => 0b11,
+        }
+    }
+
+    /// Returns the data of the VCOM and data interval setting register `cdi` with the border
+    /// selection changed to the color, keeping the other bits.
+    fn encode(self, controller: Controller, colors: PanelColors, cdi: &[u8]) -> Vec<u8> {
+        let mut data = cdi.to_vec();
+        let lut = self.lut(colors, data_inverted(controller, cdi));
+        match controller {
+            // BDZ (bit 7) floats the border, BDV (bits 5-4) selects its LUT, DDX (bits 1-0)
+            Controller::Uc8179 => {
+                let bdz = if self == BorderColor::Floating {
+                    0x80
+                } else {
+                    0
+                };
+                data[0] = cdi[0] & 0x4F | bdz | lut << 4;
+            }
+            // VBD (bits 7-6) selects the LUT of the border, DDX (bits 5-4)
+            Controller::Uc8151 => data[0] = cdi[0] & 0x3F | lut << 6,
+        }
+        data
+    }
+}
+
+/// Returns whether set bits in the black/white data RAM are white, which is selected by the data
+/// polarity (DDX\[0\]) of the VCOM and data interval setting register `cdi`.
+pub(crate) fn data_inverted(controller: Controller, cdi: &[u8]) -> bool {
+    match controller {
+        Controller::Uc8179 => cdi[0] & 0x01 != 0,
+        Controller::Uc8151 => cdi[0] & 0x10 != 0,
+    }
+}
+
+/// Returns whether set bits in the black/white data RAM are white after `commands` have been
+/// sent, falling back to [`EpdConfig::inverted`] if they do not write the VCOM and data interval
+/// setting.
+pub(crate) fn commands_inverted(config: &EpdConfig, commands: &[Action]) -> bool {
+    match register_index(commands, VCOM_DATA_INTERVAL).map(|i| &commands[i]) {
+        Some(Action::SendData(cdi)) => data_inverted(config.controller, cdi),
+        _ => config.inverted,
+    }
+}
+
+/// Encodes a VCOM DC level in volts for the VCOM DC setting register (`0x82`), which sets it from
+/// -0.10 V down in steps of 50 mV. Returns `None` if the controller cannot drive the level.
+pub(crate) fn vcom_bits(controller: Controller, volts: f32) -> Option<u8> {
+    let max: u8 = match controller {
+        // -0.10 V to -4.05 V
+        Controller::Uc8179 => 0x4F,
+        // -0.10 V to -3.00 V
+        Controller::Uc8151 => 0x3A,
+    };
+    let step = ((-0.10 - volts) / 0.05).round();
+    (0.0..=f32::from(max)).contains(&step).then_some(step as u8)
+}
+
+/// Returns the largest width and height which fit in the resolution setting (`0x61`) of the
+/// controller.
+pub(crate) fn max_resolution(controller: Controller) -> (usize, usize) {
+    match controller {
+        // 10-bit HRES in multiples of 8 and 10-bit VRES
+        Controller::Uc8179 => (0x3F8, 0x3FF),
+        // 8-bit HRES in multiples of 8 and 9-bit VRES
+        Controller::Uc8151 => (0xF8, 0x1FF),
+    }
+}
+
+/// Register values which replace those written by the init commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Registers {
+    pub(crate) panel_setting: Option<PanelSetting>,
+    pub(crate) border: Option<BorderColor>,
+    pub(crate) vcom: Option<u8>,
+    pub(crate) resolution: Option<(usize, usize)>,
+}
+
+impl Registers {
+    /// Writes the registers into `commands`. The panel setting, the border and the resolution
+    /// replace the data of the commands which set them, while the VCOM level is added to commands
+    /// which do not. [`Action::SendResolution`] already sends the resolution of `config`.
+    pub(crate) fn apply(&self, config: &EpdConfig, commands: &mut Vec<Action>) {
+        if let Some(setting) = self.panel_setting {
+            if let Some(Action::SendData(data)) =
+                register_index(commands, PANEL_SETTING).map(|i| &mut commands[i])
+            {
+                let mut psr = data.to_vec();
+                psr[0] = psr[0] & PSR_RES | setting.bits();
+                *data = Cow::Owned(psr);
+            }
+        }
+        if let Some(border) = self.border {
+            if let Some(Action::SendData(data)) =
+                register_index(commands, VCOM_DATA_INTERVAL).map(|i| &mut commands[i])
+            {
+                *data = Cow::Owned(border.encode(config.controller, config.colors, data));
+            }
+        }
+        if let Some((width, height)) = self.resolution {
+            if let Some(Action::SendData(data)) =
+                register_index(commands, RESOLUTION_SETTING).map(|i| &mut commands[i])
+            {
+                *data = Cow::Owned(config.controller.resolution(width, height));
+            }
+        }
+        if let Some(vcom) = self.vcom {
+            match register_index(commands, VCOM_DC).map(|i| &mut commands[i]) {
+                Some(Action::SendData(data)) => *data = Cow::Owned(vec![vcom]),
+                _ => commands.extend([
+                    Action::SendCommand(VCOM_DC),
+                    Action::SendData(Cow::Owned(vec![vcom])),
+                ]),
+            }
+        }
+    }
+}
+
+/// Returns the index of the data of the first `command` in `commands`.
+pub(crate) fn register_index(commands: &[Action], command: u8) -> Option<usize> {
+    commands
+        .windows(2)
+        .position(|pair| {
+            matches!(pair, [Action::SendCommand(c), Action::SendData(data)] if *c == command && !data.is_empty())
+        })
+        .map(|i| i + 1)
+}
@@ -1,5 +1,13 @@
-use rppal::gpio::{Gpio, InputPin, OutputPin};
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+//! Contains the Raspberry Pi implementation of [`EpdInterface`].
+use crate::interface::EpdInterface;
+use crate::EpdError;
+use rppal::gpio::{Gpio, InputPin, Level, OutputPin, Trigger};
+use rppal::spi::{Mode, Spi};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::sleep;
+use std::time::Duration;
+
+pub use rppal::spi::{Bus, SlaveSelect};
 
 // RPi constants
 pub(crate) const RST_PIN: u8 = 17;
@@ -7,41 +15,429 @@ pub(crate) const DC_PIN: u8 = 25;
 pub(crate) const CS_PIN: u8 = 8;
 pub(crate) const BUSY_PIN: u8 = 24;
 pub(crate) const PWR_PIN: u8 = 18;
+pub(crate) const SPI_CLOCK_SPEED: u32 = 4_000_000;
+/// Half a clock period while bit-banging reads from the display.
+const READ_CLOCK_DELAY: Duration = Duration::from_micros(1);
+
+/// Describes how the display is wired to the Raspberry Pi.
+///
+/// The default values match the Waveshare e-Paper Driver HAT. Pins are addressed by their BCM GPIO
+/// numbers.
+///
+/// # Available Options
+///
+/// | Option name | Type | Description | Default value |
+/// |---|---|---|---|
+/// | `rst_pin` | `u8` | Pin connected to RST. | 17 |
+/// | `dc_pin` | `u8` | Pin connected to DC. | 25 |
+/// | `cs_pin` | `Option<u8>` | Pin driven as CS. Set to `None` to leave chip select to the SPI controller's slave select line. | `Some(8)` |
+/// | `busy_pin` | `u8` | Pin connected to BUSY. | 24 |
+/// | `pwr_pin` | `Option<u8>` | Pin which switches the display's power. Set to `None` for boards without one. | `Some(18)` |
+/// | `spi_bus` | [`Bus`] | SPI bus the display is connected to. | [`Spi0`](Bus::Spi0) |
+/// | `slave_select` | [`SlaveSelect`] | Slave select line of the SPI bus. | [`Ss0`](SlaveSelect::Ss0) |
+/// | `spi_clock_speed` | `u32` | SPI clock speed in Hz. | 4000000 |
+///
+/// # Examples
+///
+/// ```no_run
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+/// use waveshare_rpi::rpi_helper::{Bus, HardwareConfig, SlaveSelect};
+///
+/// let hardware_config = HardwareConfig {
+///     spi_bus: Bus::Spi1,
+///     slave_select: SlaveSelect::Ss0,
+///     cs_pin: None,
+///     pwr_pin: None,
+///     ..Default::default()
+/// };
+/// let epd = Epd::with_hardware_config(EPD_CONFIG, hardware_config).unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardwareConfig {
+    pub rst_pin: u8,
+    pub dc_pin: u8,
+    pub cs_pin: Option<u8>,
+    pub busy_pin: u8,
+    pub pwr_pin: Option<u8>,
+    pub spi_bus: Bus,
+    pub slave_select: SlaveSelect,
+    pub spi_clock_speed: u32,
+}
+
+impl HardwareConfig {
+    /// Creates a new HardwareConfig struct with default values.
+    pub fn new() -> HardwareConfig {
+        Default::default()
+    }
+}
+
+impl Default for HardwareConfig {
+    fn default() -> Self {
+        HardwareConfig {
+            rst_pin: RST_PIN,
+            dc_pin: DC_PIN,
+            cs_pin: Some(CS_PIN),
+            busy_pin: BUSY_PIN,
+            pwr_pin: Some(PWR_PIN),
+            spi_bus: Bus::Spi0,
+            slave_select: SlaveSelect::Ss0,
+            spi_clock_speed: SPI_CLOCK_SPEED,
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The GPIO and SPI peripherals of the Raspberry Pi, shared by several displays.
+///
+/// Every display gets an [`RpiGpio`] of its own from [`device`](RpiBus::device), which claims its
+/// RST, DC, BUSY and CS pins. The SPI devices and the PWR pins are opened once and shared by the
+/// displays which use them, and only one display talks on the SPI buses at a time: a display
+/// keeps the buses from the moment its CS goes low until it goes high again. A shared PWR pin
+/// stays high until none of its displays want power anymore.
+///
+/// `RpiBus` is cheap to clone, and the clones share the same peripherals.
+///
+/// # Examples
+///
+/// Two displays on CE0 and CE1 of the same bus:
+///
+/// ```no_run
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+/// use waveshare_rpi::rpi_helper::{HardwareConfig, RpiBus};
+///
+/// let bus = RpiBus::new().unwrap();
+/// let left = bus.device(HardwareConfig::default()).unwrap();
+/// let right = bus
+///     .device(HardwareConfig {
+///         rst_pin: 5,
+///         dc_pin: 6,
+///         cs_pin: Some(7),
+///         busy_pin: 13,
+///         ..Default::default()
+///     })
+///     .unwrap();
+/// let mut left = Epd::with_interface(EPD_CONFIG, left).unwrap();
+/// let mut right = Epd::with_interface(EPD_CONFIG, right).unwrap();
+/// left.clear().unwrap();
+/// right.clear().unwrap();
+/// ```
+#[derive(Clone)]
+pub struct RpiBus {
+    shared: Arc<SharedBus>,
+}
+
+struct SharedBus {
+    gpio: Gpio,
+    spi_devices: Mutex<Vec<SpiDevice>>,
+    pwr_pins: Mutex<Vec<Arc<Mutex<SharedPwr>>>>,
+    lock: Arc<BusLock>,
+}
+
+struct SpiDevice {
+    bus: Bus,
+    slave_select: SlaveSelect,
+    spi: Arc<Mutex<SharedSpi>>,
+}
+
+struct SharedSpi {
+    spi: Spi,
+    clock_speed: u32,
+}
+
+struct SharedPwr {
+    pin: OutputPin,
+    number: u8,
+    /// Number of displays which want the power on.
+    users: usize,
+}
+
+impl RpiBus {
+    /// Opens the GPIO peripheral. SPI devices are opened as needed by
+    /// [`device`](RpiBus::device).
+    pub fn new() -> Result<Self, EpdError> {
+        Ok(Self {
+            shared: Arc::new(SharedBus {
+                gpio: Gpio::new()?,
+                spi_devices: Mutex::new(Vec::new()),
+                pwr_pins: Mutex::new(Vec::new()),
+                lock: Arc::default(),
+            }),
+        })
+    }
+
+    /// Claims the pins of a display wired up as described by `config`, sharing the SPI device and
+    /// the PWR pin with the other displays on this bus.
+    /// Returns `Err(EpdError::PinBusy)` if one of its RST, DC, CS or BUSY pins is in use.
+    pub fn device(&self, config: HardwareConfig) -> Result<RpiGpio, EpdError> {
+        RpiGpio::on_bus(self.clone(), config)
+    }
+
+    fn spi(&self, config: &HardwareConfig) -> Result<Arc<Mutex<SharedSpi>>, EpdError> {
+        let mut devices = lock(&self.shared.spi_devices);
+        if let Some(device) = devices
+            .iter()
+            .find(|d| d.bus == config.spi_bus && d.slave_select == config.slave_select)
+        {
+            return Ok(device.spi.clone());
+        }
+        let spi = Arc::new(Mutex::new(SharedSpi {
+            spi: Spi::new(
+                config.spi_bus,
+                config.slave_select,
+                config.spi_clock_speed,
+                Mode::Mode0,
+            )?,
+            clock_speed: config.spi_clock_speed,
+        }));
+        devices.push(SpiDevice {
+            bus: config.spi_bus,
+            slave_select: config.slave_select,
+            spi: spi.clone(),
+        });
+        Ok(spi)
+    }
+
+    fn pwr(&self, number: u8) -> Result<Arc<Mutex<SharedPwr>>, EpdError> {
+        let mut pins = lock(&self.shared.pwr_pins);
+        if let Some(pwr) = pins.iter().find(|pwr| lock(pwr).number == number) {
+            return Ok(pwr.clone());
+        }
+        let pwr = Arc::new(Mutex::new(SharedPwr {
+            pin: self.shared.gpio.get(number)?.into_output_low(),
+            number,
+            users: 0,
+        }));
+        pins.push(pwr.clone());
+        Ok(pwr)
+    }
+}
+
+/// Lets only one display at a time talk on the SPI buses of an [`RpiBus`].
+#[derive(Default)]
+pub(crate) struct BusLock {
+    /// Whether a display is between lowering and raising its CS.
+    claimed: Mutex<bool>,
+    released: Condvar,
+}
+
+impl BusLock {
+    /// Waits until no other display is using the SPI buses and takes them.
+    fn claim(&self) {
+        let mut claimed = lock(&self.claimed);
+        while *claimed {
+            claimed = self
+                .released
+                .wait(claimed)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *claimed = true;
+    }
+
+    fn release(&self) {
+        *lock(&self.claimed) = false;
+        self.released.notify_one();
+    }
+}
+
+/// The hold of one display on a [`BusLock`], which follows the display's CS.
+pub(crate) struct BusClaim {
+    lock: Arc<BusLock>,
+    /// Whether this display holds the SPI buses.
+    held: bool,
+}
+
+impl BusClaim {
+    pub(crate) fn new(lock: Arc<BusLock>) -> Self {
+        Self { lock, held: false }
+    }
+
+    /// Drives CS with `drive`, taking the SPI buses before it goes low and giving them back once
+    /// it is high again.
+    pub(crate) fn set_cs(&mut self, high: bool, drive: impl FnOnce()) {
+        if !high && !self.held {
+            self.lock.claim();
+            self.held = true;
+        }
+        drive();
+        if high && self.held {
+            self.lock.release();
+            self.held = false;
+        }
+    }
+}
 
 pub(crate) struct RpiGpioPins {
     pub rst: OutputPin,
     pub dc: OutputPin,
-    pub cs: OutputPin,
+    pub cs: Option<OutputPin>,
     pub busy: InputPin,
-    pub pwr: OutputPin,
 }
 
 /// A struct that contains all the interfaces required to interact with an E-Paper Display
-pub(crate) struct RpiGpio {
-    pub gpio: RpiGpioPins,
-    pub spi: Spi,
+///
+/// Reading from the display is supported with a GPIO driven CS pin on the SPI buses 0 and 1. As
+/// the display answers on the data line connected to MOSI, the SPI clock and MOSI pins are
+/// switched to GPIOs for the duration of a read and the bits are clocked in by hand.
+///
+/// To drive several displays from one Raspberry Pi, create their `RpiGpio`s with
+/// [`RpiBus::device`].
+pub struct RpiGpio {
+    pub(crate) gpio: RpiGpioPins,
+    bus: RpiBus,
+    spi: Arc<Mutex<SharedSpi>>,
+    spi_bus: Bus,
+    spi_clock_speed: u32,
+    pwr: Option<Arc<Mutex<SharedPwr>>>,
+    /// Whether this display wants the PWR pin high.
+    pwr_high: bool,
+    claim: BusClaim,
+    busy_interrupt: bool,
 }
 
 impl RpiGpio {
-    pub(crate) fn new() -> Self {
-        let gpio = Gpio::new().unwrap();
-        let spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 4_000_000, Mode::Mode0).unwrap();
-        let mut pwr = gpio.get(PWR_PIN).unwrap().into_output();
-        pwr.set_high();
-        let rst = gpio.get(RST_PIN).unwrap().into_output();
-        let dc = gpio.get(DC_PIN).unwrap().into_output();
-        let cs = gpio.get(CS_PIN).unwrap().into_output();
-        let busy = gpio.get(BUSY_PIN).unwrap().into_input();
-        RpiGpio {
-            gpio: RpiGpioPins {
-                rst,
-                dc,
-                cs,
-                busy,
-                pwr,
-            },
+    /// Claims the GPIO pins and SPI bus used by the Waveshare e-Paper Driver HAT.
+    pub fn new() -> Result<Self, EpdError> {
+        Self::with_config(HardwareConfig::default())
+    }
+
+    /// Claims the GPIO pins and SPI bus described by `config`. The SPI bus is not shared with
+    /// other displays, use [`RpiBus`] for that.
+    pub fn with_config(config: HardwareConfig) -> Result<Self, EpdError> {
+        Self::on_bus(RpiBus::new()?, config)
+    }
+
+    fn on_bus(bus: RpiBus, config: HardwareConfig) -> Result<Self, EpdError> {
+        let gpio = &bus.shared.gpio;
+        let rst = gpio.get(config.rst_pin)?.into_output();
+        let dc = gpio.get(config.dc_pin)?.into_output();
+        let cs = match config.cs_pin {
+            Some(pin) => Some(gpio.get(pin)?.into_output()),
+            None => None,
+        };
+        let mut busy = gpio.get(config.busy_pin)?.into_input();
+        let busy_interrupt = match busy.set_interrupt(Trigger::Both) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!(
+                    "Unable to use interrupts on the busy pin, falling back to polling: {e}"
+                );
+                false
+            }
+        };
+        let spi = bus.spi(&config)?;
+        let pwr = match config.pwr_pin {
+            Some(pin) => Some(bus.pwr(pin)?),
+            None => None,
+        };
+        let claim = BusClaim::new(bus.shared.lock.clone());
+        let mut s = RpiGpio {
+            gpio: RpiGpioPins { rst, dc, cs, busy },
+            bus,
             spi,
+            spi_bus: config.spi_bus,
+            spi_clock_speed: config.spi_clock_speed,
+            pwr,
+            pwr_high: false,
+            claim,
+            busy_interrupt,
+        };
+        s.set_pwr(true)?;
+        Ok(s)
+    }
+}
+
+impl EpdInterface for RpiGpio {
+    fn set_rst(&mut self, high: bool) -> Result<(), EpdError> {
+        self.gpio.rst.write(high.into());
+        Ok(())
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<(), EpdError> {
+        self.gpio.dc.write(high.into());
+        Ok(())
+    }
+
+    fn set_cs(&mut self, high: bool) -> Result<(), EpdError> {
+        let cs = &mut self.gpio.cs;
+        self.claim.set_cs(high, || {
+            if let Some(cs) = cs {
+                cs.write(high.into());
+            }
+        });
+        Ok(())
+    }
+
+    fn set_pwr(&mut self, high: bool) -> Result<(), EpdError> {
+        let Some(pwr) = &self.pwr else {
+            return Ok(());
+        };
+        if high != self.pwr_high {
+            let mut pwr = lock(pwr);
+            if high {
+                pwr.users += 1;
+            } else {
+                pwr.users -= 1;
+            }
+            let level = pwr.users > 0;
+            pwr.pin.write(level.into());
+            self.pwr_high = high;
+        }
+        Ok(())
+    }
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<(), EpdError> {
+        let mut spi = lock(&self.spi);
+        // Displays on the same SPI device may run at different clock speeds
+        if spi.clock_speed != self.spi_clock_speed {
+            spi.spi.set_clock_speed(self.spi_clock_speed)?;
+            spi.clock_speed = self.spi_clock_speed;
+        }
+        spi.spi.write(data)?;
+        Ok(())
+    }
+
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<(), EpdError> {
+        let (sclk_pin, mosi_pin) = match self.spi_bus {
+            Bus::Spi0 => (11, 10),
+            Bus::Spi1 => (21, 20),
+            _ => return Err(EpdError::Unsupported("reading on this SPI bus")),
+        };
+        if self.gpio.cs.is_none() {
+            return Err(EpdError::Unsupported("reading with hardware chip select"));
+        }
+        // Both pins go back to the SPI controller when they are dropped
+        let gpio = &self.bus.shared.gpio;
+        let mut sclk = gpio.get(sclk_pin)?.into_output_low();
+        let sda = gpio.get(mosi_pin)?.into_input();
+        for byte in buffer.iter_mut() {
+            *byte = 0;
+            for _ in 0..8 {
+                sclk.set_high();
+                sleep(READ_CLOCK_DELAY);
+                *byte = *byte << 1 | u8::from(sda.read() == Level::High);
+                sclk.set_low();
+                sleep(READ_CLOCK_DELAY);
+            }
+        }
+        Ok(())
+    }
+
+    fn busy_high(&mut self) -> Result<bool, EpdError> {
+        Ok(self.gpio.busy.read() == Level::High)
+    }
+
+    fn wait_for_busy_edge(&mut self, timeout: Duration) -> Result<(), EpdError> {
+        if !self.busy_interrupt {
+            sleep(timeout);
+            return Ok(());
         }
+        // Edges which happened since the last poll are not discarded, so a level change between
+        // reading the pin and calling this function is not missed
+        self.gpio.busy.poll_interrupt(false, Some(timeout))?;
+        Ok(())
     }
 }
 
@@ -50,6 +446,8 @@ impl Drop for RpiGpio {
     fn drop(&mut self) {
         self.gpio.rst.set_low();
         self.gpio.dc.set_low();
-        self.gpio.pwr.set_low();
+        // Other displays may still need the power and the bus
+        let _ = self.set_pwr(false);
+        let _ = self.set_cs(true);
     }
 }
@@ -0,0 +1,370 @@
+//! Contains an in-memory simulation of the display controller for testing without hardware.
+use crate::epd_configs::{BusyPolarity, Controller, EpdConfig};
+use crate::interface::EpdInterface;
+use crate::registers::{self, VCOM_DATA_INTERVAL};
+use crate::{EpdError, Revision, Status};
+use image::{DynamicImage, GrayImage, ImageResult, Rgb, RgbImage};
+use std::collections::HashMap;
+use std::path::Path;
+
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const RED: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// A simulated e-paper controller which implements [`EpdInterface`].
+///
+/// The simulator interprets the command and data bytes sent by [`Epd`](crate::Epd) the same way
+/// the controller on the display would, keeping track of the two data RAMs (written with `0x10`
+/// and `0x13`) and the image which is currently visible on the "panel". The visible image is only
+/// updated on a display refresh (`0x12`) while the controller is powered on (`0x04`), and a deep
+/// sleep (`0x07`, `0xA5`) makes the controller ignore everything until the next hardware reset.
+/// Partial refreshes through the partial window commands (`0x90`, `0x91`, `0x92`) only write to
+/// and refresh the area inside the window, and the scan direction bits of the panel setting
+/// (`0x00`) flip the image on the panel. The data polarity bit (DDX\[0\]) of the VCOM and data
+/// interval setting (`0x50`) selects whether set bits in the black/white data RAM are black or
+/// white.
+///
+/// # Examples
+///
+/// ```
+/// use waveshare_rpi::{epd_configs::epd7in5_v2::EPD_CONFIG, Epd};
+/// use waveshare_rpi::simulator::EpdSimulator;
+///
+/// let mut epd = Epd::with_interface(EPD_CONFIG, EpdSimulator::new(&EPD_CONFIG)).unwrap();
+/// epd.clear().unwrap();
+/// assert!(epd.interface().visible_image().pixels().all(|p| p.0 == [255]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct EpdSimulator {
+    width: usize,
+    height: usize,
+    busy_polarity: BusyPolarity,
+    /// Data polarity used until the VCOM and data interval setting is written.
+    inverted: bool,
+    controller: Controller,
+    temperature: f32,
+    revision: Revision,
+    rst: bool,
+    dc: bool,
+    cs: bool,
+    pwr: bool,
+    command: Option<u8>,
+    data_index: usize,
+    powered_on: bool,
+    deep_sleep: bool,
+    old_ram: Vec<u8>,
+    new_ram: Vec<u8>,
+    visible: RgbImage,
+    refresh_count: usize,
+    hold_busy: bool,
+    partial: bool,
+    registers: HashMap<u8, Vec<u8>>,
+}
+
+impl EpdSimulator {
+    /// Creates a new simulator for a display with the dimensions described by `config`.
+    pub fn new(config: &EpdConfig) -> Self {
+        let buffer_size = config.width * config.height / 8;
+        Self {
+            width: config.width,
+            height: config.height,
+            busy_polarity: config.busy_polarity,
+            inverted: config.inverted,
+            controller: config.controller,
+            temperature: 20.0,
+            revision: Revision {
+                product: 0x000001,
+                lut: 0x000001,
+                chip: 0x01,
+            },
+            rst: true,
+            dc: false,
+            cs: true,
+            pwr: true,
+            command: None,
+            data_index: 0,
+            powered_on: false,
+            deep_sleep: false,
+            old_ram: vec![0x00; buffer_size],
+            new_ram: vec![0x00; buffer_size],
+            visible: RgbImage::from_pixel(config.width as u32, config.height as u32, WHITE),
+            refresh_count: 0,
+            hold_busy: false,
+            partial: false,
+            registers: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the controller is in deep sleep and waiting for a hardware reset.
+    pub fn is_deep_sleep(&self) -> bool {
+        self.deep_sleep
+    }
+
+    /// Returns true if the controller has been powered on with `0x04`.
+    pub fn is_powered_on(&self) -> bool {
+        self.powered_on
+    }
+
+    /// Returns true if the PWR pin is high, i.e. the display is supplied with power.
+    pub fn is_pwr_high(&self) -> bool {
+        self.pwr
+    }
+
+    /// Returns the data last written with `command` since the last reset, for every command other
+    /// than the data RAM writes.
+    pub fn register(&self, command: u8) -> Option<&[u8]> {
+        self.registers.get(&command).map(Vec::as_slice)
+    }
+
+    /// Sets the temperature in °C reported by the simulated temperature sensor. Defaults to 20 °C.
+    pub fn set_temperature(&mut self, celsius: f32) {
+        self.temperature = celsius;
+    }
+
+    /// Sets the revision numbers answered to the revision command (`0x70`). Every revision is 1
+    /// by default.
+    pub fn set_revision(&mut self, revision: Revision) {
+        self.revision = revision;
+    }
+
+    /// Returns the number of display refreshes which actually updated the visible image.
+    pub fn refresh_count(&self) -> usize {
+        self.refresh_count
+    }
+
+    /// Keeps the BUSY pin busy until called again with `false`, simulating a wedged or
+    /// disconnected display.
+    pub fn hold_busy(&mut self, hold: bool) {
+        self.hold_busy = hold;
+    }
+
+    /// Returns the image currently visible on the simulated panel in grayscale, black pixels
+    /// being 0 and white pixels being 255. Pixels in 4-level grayscale are 0, 85, 170 or 255.
+    pub fn visible_image(&self) -> GrayImage {
+        DynamicImage::ImageRgb8(self.visible.clone()).into_luma8()
+    }
+
+    /// Returns the image currently visible on the simulated panel in color, for displays which
+    /// are able to show red.
+    pub fn visible_color_image(&self) -> RgbImage {
+        self.visible.clone()
+    }
+
+    /// Saves the image currently visible on the simulated panel as a PNG file.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.visible.save_with_format(path, image::ImageFormat::Png)
+    }
+
+    fn handle_command(&mut self, command: u8) {
+        if self.deep_sleep {
+            return;
+        }
+        self.command = Some(command);
+        self.data_index = 0;
+        if command != 0x10 && command != 0x13 {
+            self.registers.insert(command, Vec::new());
+        }
+        match command {
+            0x02 => self.powered_on = false,
+            0x04 => self.powered_on = true,
+            0x12 => self.refresh(),
+            0x91 => self.partial = true,
+            0x92 => self.partial = false,
+            _ => {}
+        }
+    }
+
+    /// Returns the area the RAM writes and refreshes apply to as the range of bytes in each row
+    /// and the range of rows.
+    fn active_area(&self) -> (std::ops::Range<usize>, std::ops::Range<usize>) {
+        let row_bytes = self.width / 8;
+        if !self.partial {
+            return (0..row_bytes, 0..self.height);
+        }
+        let mut w = [0x00; 8];
+        if let Some(window) = self.register(0x90) {
+            let len = window.len().min(8);
+            w[..len].copy_from_slice(&window[..len]);
+        }
+        let x_start = u16::from_be_bytes([w[0], w[1]]) as usize;
+        let x_end = u16::from_be_bytes([w[2], w[3]]) as usize;
+        let y_start = u16::from_be_bytes([w[4], w[5]]) as usize;
+        let y_end = u16::from_be_bytes([w[6], w[7]]) as usize;
+        (
+            x_start / 8..(x_end / 8 + 1).min(row_bytes),
+            y_start..(y_end + 1).min(self.height),
+        )
+    }
+
+    /// Maps the n-th byte written to a data RAM to its position in the RAM.
+    fn ram_index(&self, n: usize) -> Option<usize> {
+        let (cols, rows) = self.active_area();
+        let row = rows.start + n / cols.len();
+        if row >= rows.end {
+            return None;
+        }
+        Some(row * self.width / 8 + cols.start + n % cols.len())
+    }
+
+    fn handle_data(&mut self, byte: u8) {
+        if self.deep_sleep {
+            return;
+        }
+        let index = self.data_index;
+        self.data_index += 1;
+        match self.command {
+            Some(0x07) if byte == 0xA5 => self.deep_sleep = true,
+            Some(0x10) => {
+                if let Some(i) = self.ram_index(index) {
+                    self.old_ram[i] = byte;
+                }
+            }
+            Some(0x13) => {
+                if let Some(i) = self.ram_index(index) {
+                    self.new_ram[i] = byte;
+                }
+            }
+            Some(command) => {
+                if let Some(register) = self.registers.get_mut(&command) {
+                    register.push(byte);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn refresh(&mut self) {
+        if !self.powered_on {
+            log::warn!("Simulated EPD was refreshed while powered off");
+            return;
+        }
+        // Forcing the temperature to 0x5F selects the 4-level grayscale waveform
+        let gray4 = self.register(0xE5) == Some(&[0x5F]);
+        // Bit 4 of the panel setting selects between black/white and black/white/red mode
+        let red = matches!(self.register(0x00), Some(&[psr, ..]) if psr & 0x10 == 0);
+        // DDX[0] makes set bits in the black/white data RAM white
+        let inverted = match self.register(VCOM_DATA_INTERVAL) {
+            Some(cdi) if !cdi.is_empty() => registers::data_inverted(self.controller, cdi),
+            _ => self.inverted,
+        };
+        let black = !inverted;
+        // Clearing the SHL and UD bits of the panel setting flips the panel's scan direction
+        let psr = self.register(0x00).and_then(|psr| psr.first()).copied();
+        let flip_x = psr.is_some_and(|psr| psr & 0x04 == 0);
+        let flip_y = psr.is_some_and(|psr| psr & 0x08 == 0);
+        let (cols, rows) = self.active_area();
+        for y in rows {
+            for x in cols.start * 8..cols.end * 8 {
+                let i = y * self.width + x;
+                let mask = 0x80 >> (i % 8);
+                let new = self.new_ram[i / 8] & mask != 0;
+                let old = self.old_ram[i / 8] & mask != 0;
+                let pixel = match (red, gray4, new, old) {
+                    (true, _, true, _) => RED,
+                    (true, _, false, old) if old == black => BLACK,
+                    (true, _, false, _) => WHITE,
+                    (false, true, new, old) => {
+                        let darkness = (new as u8) << 1 | old as u8;
+                        Rgb([255 - darkness * 85; 3])
+                    }
+                    (false, false, new, _) if new == black => BLACK,
+                    (false, false, _, _) => WHITE,
+                };
+                let panel_x = if flip_x { self.width - 1 - x } else { x };
+                let panel_y = if flip_y { self.height - 1 - y } else { y };
+                self.visible
+                    .put_pixel(panel_x as u32, panel_y as u32, pixel);
+            }
+        }
+        self.refresh_count += 1;
+    }
+}
+
+impl EpdInterface for EpdSimulator {
+    fn set_rst(&mut self, high: bool) -> Result<(), EpdError> {
+        // The controller resets on the rising edge of RST
+        if high && !self.rst {
+            self.deep_sleep = false;
+            self.powered_on = false;
+            self.partial = false;
+            self.command = None;
+            self.registers.clear();
+        }
+        self.rst = high;
+        Ok(())
+    }
+
+    fn set_dc(&mut self, high: bool) -> Result<(), EpdError> {
+        self.dc = high;
+        Ok(())
+    }
+
+    fn set_cs(&mut self, high: bool) -> Result<(), EpdError> {
+        self.cs = high;
+        Ok(())
+    }
+
+    fn set_pwr(&mut self, high: bool) -> Result<(), EpdError> {
+        // Cutting the power loses everything but the image on the panel itself
+        if !high {
+            self.deep_sleep = false;
+            self.powered_on = false;
+            self.command = None;
+        }
+        self.pwr = high;
+        Ok(())
+    }
+
+    fn spi_write(&mut self, data: &[u8]) -> Result<(), EpdError> {
+        if self.cs || !self.rst || !self.pwr {
+            return Ok(());
+        }
+        for &byte in data {
+            if self.dc {
+                self.handle_data(byte);
+            } else {
+                self.handle_command(byte);
+            }
+        }
+        Ok(())
+    }
+
+    fn spi_read(&mut self, buffer: &mut [u8]) -> Result<(), EpdError> {
+        buffer.fill(0x00);
+        if self.cs || !self.rst || !self.pwr || self.deep_sleep || !self.dc {
+            return Ok(());
+        }
+        let response = match self.command {
+            Some(0x40) => {
+                let whole = self.temperature.floor();
+                let fraction = match self.controller {
+                    Controller::Uc8179 => (((self.temperature - whole) * 8.0) as u8) << 5,
+                    Controller::Uc8151 => (((self.temperature - whole) * 2.0) as u8) << 7,
+                };
+                vec![whole as i8 as u8, fraction]
+            }
+            Some(0x70) => self.revision.to_bytes().to_vec(),
+            Some(0x71) => vec![Status {
+                busy: self.hold_busy,
+                powered_off: !self.powered_on,
+                powered_on: self.powered_on,
+                partial: self.partial,
+                ..Default::default()
+            }
+            .bits()],
+            _ => return Ok(()),
+        };
+        for byte in buffer.iter_mut() {
+            *byte = response.get(self.data_index).copied().unwrap_or(0x00);
+            self.data_index += 1;
+        }
+        Ok(())
+    }
+
+    fn busy_high(&mut self) -> Result<bool, EpdError> {
+        Ok(match self.busy_polarity {
+            BusyPolarity::ActiveLow => !self.hold_busy,
+            BusyPolarity::ActiveHigh => self.hold_busy,
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use std::borrow::Cow;
+
+/// Transposition and flips which map image data from the orientation seen by the user to the
+/// layout of the display's data RAM.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Transform {
+    /// Swaps rows and columns, which is applied before the flips.
+    pub transpose: bool,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl Transform {
+    /// Returns the dimensions of a `width` by `height` block after the transform.
+    pub fn dimensions(&self, width: usize, height: usize) -> (usize, usize) {
+        if self.transpose {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Returns the position of a `width` by `height` block at (`x`, `y`) within an image of
+    /// `image_width` by `image_height` pixels after the transform.
+    pub fn position(
+        &self,
+        (x, y, width, height): (usize, usize, usize, usize),
+        (image_width, image_height): (usize, usize),
+    ) -> (usize, usize) {
+        let (mut x, mut y) = if self.transpose { (y, x) } else { (x, y) };
+        let (width, height) = self.dimensions(width, height);
+        let (image_width, image_height) = self.dimensions(image_width, image_height);
+        if self.flip_horizontal {
+            x = image_width - x - width;
+        }
+        if self.flip_vertical {
+            y = image_height - y - height;
+        }
+        (x, y)
+    }
+
+    /// Applies the transform to a plane of `width` by `height` pixels with rows padded to whole
+    /// bytes.
+    pub fn apply<'a>(&self, data: &'a [u8], width: usize, height: usize) -> Cow<'a, [u8]> {
+        let row_bytes = width.div_ceil(8);
+        if !self.transpose && !self.flip_horizontal {
+            if !self.flip_vertical {
+                return Cow::Borrowed(data);
+            }
+            // Whole rows can be copied when only flipping vertically
+            return Cow::Owned(data.chunks(row_bytes).rev().flatten().copied().collect());
+        }
+
+        let (out_width, out_height) = self.dimensions(width, height);
+        let out_row_bytes = out_width.div_ceil(8);
+        let mut out = vec![0x00; out_row_bytes * out_height];
+        for out_y in 0..out_height {
+            let y = if self.flip_vertical {
+                out_height - 1 - out_y
+            } else {
+                out_y
+            };
+            for out_x in 0..out_width {
+                let x = if self.flip_horizontal {
+                    out_width - 1 - out_x
+                } else {
+                    out_x
+                };
+                let (src_x, src_y) = if self.transpose { (y, x) } else { (x, y) };
+                if data[src_y * row_bytes + src_x / 8] & (0x80 >> (src_x % 8)) != 0 {
+                    out[out_y * out_row_bytes + out_x / 8] |= 0x80 >> (out_x % 8);
+                }
+            }
+        }
+        Cow::Owned(out)
+    }
+}
@@ -0,0 +1,60 @@
+//! Contains the waits of the command sequences, which are shared by [`Epd`](crate::Epd) and the
+//! async variant of it.
+//!
+//! The sequences are written once as futures which are generic over a [`Wait`]. [`Epd`](crate::Epd)
+//! runs them with [`Blocking`] waits, which sleep the thread and never suspend, so
+//! [`block_on`] completes them with a single poll.
+use crate::interface::EpdInterface;
+use crate::EpdError;
+use std::future::{self, Future};
+use std::pin::pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread;
+use std::time::Duration;
+
+/// How the command sequences wait for the display.
+pub(crate) trait Wait {
+    /// Waits for `duration`.
+    fn sleep(duration: Duration) -> impl Future<Output = ()>;
+
+    /// Waits until the BUSY pin changes level or `timeout` elapses, whichever comes first.
+    fn busy_edge<I: EpdInterface>(
+        interface: &mut I,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), EpdError>>;
+}
+
+/// Waits by blocking the thread. The wait happens when the future is created, and the future is
+/// ready right away.
+pub(crate) struct Blocking;
+
+impl Wait for Blocking {
+    fn sleep(duration: Duration) -> impl Future<Output = ()> {
+        thread::sleep(duration);
+        future::ready(())
+    }
+
+    fn busy_edge<I: EpdInterface>(
+        interface: &mut I,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<(), EpdError>> {
+        future::ready(interface.wait_for_busy_edge(timeout))
+    }
+}
+
+/// A waker which does nothing, as the sequences run by [`block_on`] are never woken up.
+const NOOP_WAKER: RawWaker = {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| NOOP_WAKER, |_| {}, |_| {}, |_| {});
+    RawWaker::new(std::ptr::null(), &VTABLE)
+};
+
+/// Runs a command sequence with [`Blocking`] waits to completion.
+pub(crate) fn block_on<F: Future>(sequence: F) -> F::Output {
+    // SAFETY: the functions of the vtable do nothing, so they are fine with any data pointer
+    let waker = unsafe { Waker::from_raw(NOOP_WAKER) };
+    let mut cx = Context::from_waker(&waker);
+    match pin!(sequence).poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("blocking waits never suspend"),
+    }
+}